@@ -2,7 +2,15 @@
 
 pub mod serializable;
 
-use std::collections::HashMap;
+use proc_macro2::TokenStream;
+use quote::quote;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CodexError {
+    #[error("{0}")]
+    Parse(#[from] syn::Error),
+}
 
 fn get_type_ident(ty: &syn::Type) -> Option<&syn::Ident> {
     if let syn::Type::Path(path) = ty {
@@ -84,6 +92,9 @@ enum MethodType {
     Create,
     Attr,
     Event,
+
+    // Acts on the live window rather than configuring it, e.g. destroy
+    Lifecycle,
 }
 
 impl MethodType {
@@ -93,10 +104,147 @@ impl MethodType {
             Self::Create
         } else if ident.starts_with("on_") {
             Self::Event
+        } else if matches!(
+            ident.as_str(),
+            "destroy" | "visible" | "redraw" | "move_offscreen" | "snapshot"
+        ) {
+            Self::Lifecycle
         } else {
             Self::Attr
         }
     }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Attr => "attr",
+            Self::Event => "event",
+            Self::Lifecycle => "lifecycle",
+        }
+    }
+}
+
+// Builder attributes trywin knows about: every `fn x(self, ...) ->
+// Result<Self, _>` on trywin::Window except the lifecycle methods, in
+// declaration order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AttrKind {
+    Text,
+    Title,
+    Bounds,
+    SetBounds,
+    CenterOnScreen,
+    CenterOnParent,
+    Background,
+    ToolWindow,
+    Chrome,
+    EnableScrollbars,
+    Rtl,
+    BeginGroup,
+    Draggable,
+    Layered,
+    TransparentColor,
+    ClickThrough,
+    DarkMode,
+    NoActivate,
+    ModifyStyle,
+    ModifyExStyle,
+    AutomationId,
+    ShowWhenReady,
+    WindowState,
+    Cursor,
+}
+
+impl AttrKind {
+    // None for unknown methods, e.g. custom extensions
+    fn from_ident(ident: &syn::Ident) -> Option<Self> {
+        Some(match ident.to_string().as_str() {
+            "text" => Self::Text,
+            "title" => Self::Title,
+            "bounds" => Self::Bounds,
+            "set_bounds" => Self::SetBounds,
+            "center_on_screen" => Self::CenterOnScreen,
+            "center_on_parent" => Self::CenterOnParent,
+            "background" => Self::Background,
+            "tool_window" => Self::ToolWindow,
+            "chrome" => Self::Chrome,
+            "enable_scrollbars" => Self::EnableScrollbars,
+            "rtl" => Self::Rtl,
+            "begin_group" => Self::BeginGroup,
+            "draggable" => Self::Draggable,
+            "layered" => Self::Layered,
+            "transparent_color" => Self::TransparentColor,
+            "click_through" => Self::ClickThrough,
+            "dark_mode" => Self::DarkMode,
+            "no_activate" => Self::NoActivate,
+            "modify_style" => Self::ModifyStyle,
+            "modify_ex_style" => Self::ModifyExStyle,
+            "automation_id" => Self::AutomationId,
+            "show_when_ready" => Self::ShowWhenReady,
+            "window_state" => Self::WindowState,
+            "cursor" => Self::Cursor,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Title => "title",
+            Self::Bounds => "bounds",
+            Self::SetBounds => "set_bounds",
+            Self::CenterOnScreen => "center_on_screen",
+            Self::CenterOnParent => "center_on_parent",
+            Self::Background => "background",
+            Self::ToolWindow => "tool_window",
+            Self::Chrome => "chrome",
+            Self::EnableScrollbars => "enable_scrollbars",
+            Self::Rtl => "rtl",
+            Self::BeginGroup => "begin_group",
+            Self::Draggable => "draggable",
+            Self::Layered => "layered",
+            Self::TransparentColor => "transparent_color",
+            Self::ClickThrough => "click_through",
+            Self::DarkMode => "dark_mode",
+            Self::NoActivate => "no_activate",
+            Self::ModifyStyle => "modify_style",
+            Self::ModifyExStyle => "modify_ex_style",
+            Self::AutomationId => "automation_id",
+            Self::ShowWhenReady => "show_when_ready",
+            Self::WindowState => "window_state",
+            Self::Cursor => "cursor",
+        }
+    }
+}
+
+// How a step in a chain unwraps its Result
+#[derive(Clone, Copy)]
+enum Fallible<'a> {
+    // .a()
+    No,
+
+    // .a()?
+    Try(&'a syn::token::Question),
+
+    // .a().unwrap() or .a().expect("...")
+    Unwrap(&'a syn::ExprMethodCall),
+}
+
+impl<'a> Fallible<'a> {
+    // .unwrap() or .expect(msg) applied directly to a method call
+    fn unwrap(expr: &'a syn::Expr) -> Option<(Self, &'a syn::Expr)> {
+        if let syn::Expr::MethodCall(call) = expr {
+            if call.attrs.is_empty()
+                && call.turbofish.is_none()
+                && matches!(&*call.receiver, syn::Expr::MethodCall(_))
+                && (call.method == "unwrap" && call.args.is_empty()
+                    || call.method == "expect" && call.args.len() == 1)
+            {
+                return Some((Self::Unwrap(call), &call.receiver));
+            }
+        }
+        None
+    }
 }
 
 // .a()
@@ -104,9 +252,72 @@ struct Method<'a> {
     dot: &'a syn::token::Dot,
     ident: &'a syn::Ident,
     method_type: MethodType,
+    attr_kind: Option<AttrKind>, // Only for MethodType::Attr
     paren: &'a syn::token::Paren,
     args: &'a syn::punctuated::Punctuated<syn::Expr, syn::token::Comma>,
-    question: &'a syn::token::Question,
+    fallible: Fallible<'a>,
+
+    // The argument of an event method, if it's a lone closure
+    closure: Option<&'a syn::ExprClosure>,
+}
+
+impl<'a> Method<'a> {
+    // End of the step, including any ? or .unwrap()
+    fn end(&self) -> proc_macro2::Span {
+        match self.fallible {
+            Fallible::No => self.paren.span.close(),
+            Fallible::Try(question) => question.span,
+            Fallible::Unwrap(call) => call.paren_token.span.close(),
+        }
+    }
+}
+
+// A variable named in #[closure(...)], e.g. "weak window"
+struct Capture {
+    mode: String, // e.g. "clone" or "weak"; may be several words
+    ident: proc_macro2::Ident,
+}
+
+// #[closure(...)], which closure_attr::with_closure rewrites. Analysis
+// sees the source before expansion, so the attribute is still there.
+fn has_closure_attr(closure: &syn::ExprClosure) -> bool {
+    closure
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("closure"))
+}
+
+// Parse closure_attr's capture lists. Unparseable lists are skipped.
+fn closure_captures(closure: &syn::ExprClosure) -> Vec<Capture> {
+    use syn::{ext::IdentExt, parse::ParseStream, punctuated::Punctuated, Token};
+
+    // Words, then the variable; words may be keywords, e.g. "ref"
+    fn capture(input: ParseStream) -> syn::Result<Capture> {
+        let mut words = vec![syn::Ident::parse_any(input)?];
+        while !input.is_empty() && !input.peek(Token![,]) {
+            words.push(syn::Ident::parse_any(input)?);
+        }
+        let ident = words.pop().unwrap();
+        let mode = words.iter().map(ToString::to_string).collect::<Vec<_>>();
+        Ok(Capture {
+            mode: mode.join(" "),
+            ident,
+        })
+    }
+
+    let mut captures = Vec::new();
+    for attr in &closure.attrs {
+        if !attr.path().is_ident("closure") {
+            continue;
+        }
+        let list = attr.parse_args_with(|input: ParseStream| {
+            Punctuated::<_, Token![,]>::parse_terminated_with(input, capture)
+        });
+        if let Ok(list) = list {
+            captures.extend(list);
+        }
+    }
+    captures
 }
 
 // x.a().b().c()
@@ -120,24 +331,44 @@ impl<'a> MethodChain<'a> {
     fn new(expr: &'a syn::Expr) -> Option<Self> {
         let mut methods = Vec::new();
         let mut expr = expr;
-        while let syn::Expr::Try(tr) = expr {
-            if !tr.attrs.is_empty() {
+        loop {
+            let (fallible, inner) = match expr {
+                syn::Expr::Try(tr) if tr.attrs.is_empty() => {
+                    (Fallible::Try(&tr.question_token), &*tr.expr)
+                }
+                _ => Fallible::unwrap(expr).unwrap_or((Fallible::No, expr)),
+            };
+            let syn::Expr::MethodCall(method_call) = inner else {
+                break;
+            };
+            if !method_call.attrs.is_empty() || method_call.turbofish.is_some() {
                 break;
             }
-            if let syn::Expr::MethodCall(method_call) = &*tr.expr {
-                if !method_call.attrs.is_empty() || method_call.turbofish.is_some() {
-                    break;
-                }
-                methods.push(Method {
-                    dot: &method_call.dot_token,
-                    ident: &method_call.method,
-                    method_type: MethodType::from_ident(&method_call.method),
-                    paren: &method_call.paren_token,
-                    args: &method_call.args,
-                    question: &tr.question_token,
-                });
-                expr = &method_call.receiver;
+            let closure = match method_call.args.first() {
+                Some(syn::Expr::Closure(closure)) if method_call.args.len() == 1 => Some(closure),
+                _ => None,
+            };
+            let mut method_type = MethodType::from_ident(&method_call.method);
+            // Only callbacks take closure_attr closures, including ones not
+            // named on_*, e.g. drag_filter
+            if method_type == MethodType::Attr && closure.is_some_and(has_closure_attr) {
+                method_type = MethodType::Event;
             }
+            methods.push(Method {
+                dot: &method_call.dot_token,
+                ident: &method_call.method,
+                method_type,
+                attr_kind: if method_type == MethodType::Attr {
+                    AttrKind::from_ident(&method_call.method)
+                } else {
+                    None
+                },
+                paren: &method_call.paren_token,
+                args: &method_call.args,
+                fallible,
+                closure: closure.filter(|_| method_type == MethodType::Event),
+            });
+            expr = &method_call.receiver;
         }
         if let Some(ident) = get_expr_ident(expr) {
             methods.reverse();
@@ -156,7 +387,9 @@ struct Definition<'a> {
     statement: &'a syn::Stmt,
     local: &'a syn::Local,
     ident: &'a syn::Ident,
+    mutable: bool, // let mut
     parent: &'a syn::Ident,
+    is_root: bool, // Created from the WindowSystem argument, e.g. new_main
     create: Method<'a>,
     methods: Vec<Method<'a>>,
 }
@@ -166,6 +399,7 @@ struct Redefinition<'a> {
     statement: &'a syn::Stmt,
     local: &'a syn::Local,
     ident: &'a syn::Ident,
+    mutable: bool, // let mut
     methods: Vec<Method<'a>>,
 }
 
@@ -174,6 +408,7 @@ struct Use<'a> {
     statement: &'a syn::Stmt,
     expr: &'a syn::Expr,
     ident: &'a syn::Ident,
+    semicolon: bool, // False for a tail expression
     methods: Vec<Method<'a>>,
 }
 
@@ -181,28 +416,39 @@ enum Item<'a> {
     Definition(Definition<'a>),
     Redefinition(Redefinition<'a>),
     Use(Use<'a>),
+
+    // Any other statement; preserved verbatim
+    Opaque(&'a syn::Stmt),
 }
 
 impl<'a> Item<'a> {
-    fn ident(&self) -> &'a syn::Ident {
+    fn ident(&self) -> Option<&'a syn::Ident> {
         match self {
-            Self::Definition(item) => item.ident,
-            Self::Redefinition(item) => item.ident,
-            Self::Use(item) => item.ident,
+            Self::Definition(item) => Some(item.ident),
+            Self::Redefinition(item) => Some(item.ident),
+            Self::Use(item) => Some(item.ident),
+            Self::Opaque(_) => None,
         }
     }
 }
 
 impl<'a> Item<'a> {
-    fn new(statement: &'a syn::Stmt) -> Option<Self> {
+    // ws_arg is the function's WindowSystem argument
+    fn new(statement: &'a syn::Stmt, ws_arg: &syn::Ident) -> Option<Self> {
         match statement {
             syn::Stmt::Local(local) => {
                 let syn::Pat::Ident(ident) = &local.pat else {
                     return None;
                 };
-                if !local.attrs.is_empty() || !ident.attrs.is_empty() || ident.subpat.is_some() {
+                // `let ref x` is rare enough to keep opaque
+                if !local.attrs.is_empty()
+                    || !ident.attrs.is_empty()
+                    || ident.by_ref.is_some()
+                    || ident.subpat.is_some()
+                {
                     return None;
                 }
+                let mutable = ident.mutability.is_some();
                 let ident = &ident.ident;
                 let Some(init) = &local.init else {
                     return None;
@@ -220,7 +466,9 @@ impl<'a> Item<'a> {
                         statement,
                         local,
                         ident,
+                        mutable,
                         parent: chain.ident,
+                        is_root: chain.ident == ws_arg,
                         create,
                         methods: chain.methods,
                     }));
@@ -230,18 +478,20 @@ impl<'a> Item<'a> {
                         statement,
                         local,
                         ident,
+                        mutable,
                         methods: chain.methods,
                     }));
                 }
                 None
             }
-            syn::Stmt::Expr(expr, _semi) => {
+            syn::Stmt::Expr(expr, semi) => {
                 if let Some(chain) = MethodChain::new(expr) {
                     if !chain.methods.is_empty() {
                         return Some(Self::Use(Use {
                             statement,
                             expr,
                             ident: chain.ident,
+                            semicolon: semi.is_some(),
                             methods: chain.methods,
                         }));
                     }
@@ -258,19 +508,19 @@ pub struct Function<'a> {
     ident: &'a syn::Ident,
     ws_type: &'a syn::Ident, // Type which implements WindowSystem
     ws_arg: &'a syn::Ident,  // Argument of type WS (references allowed)
-    items: HashMap<&'a syn::Ident, Vec<Item<'a>>>,
+    items: Vec<Item<'a>>,    // Source order
 }
 
 impl<'a> Function<'a> {
     fn new(f: &'a syn::ItemFn) -> Option<Self> {
         let ws_type = get_ws_type(f)?;
         let ws_arg = get_ws_arg(f, ws_type)?;
-        let mut items = HashMap::<&syn::Ident, Vec<_>>::new();
-        for stmt in &f.block.stmts {
-            if let Some(item) = Item::new(stmt) {
-                items.entry(item.ident()).or_default().push(item);
-            }
-        }
+        let items = f
+            .block
+            .stmts
+            .iter()
+            .map(|stmt| Item::new(stmt, ws_arg).unwrap_or(Item::Opaque(stmt)))
+            .collect();
         Some(Self {
             f,
             ident: &f.sig.ident,
@@ -281,16 +531,479 @@ impl<'a> Function<'a> {
     }
 }
 
-// TODO: error type
-pub fn get_functions(ast: &syn::File) -> Result<Vec<Function>, Box<dyn std::error::Error>> {
+/// Start of each line in a source file, for turning proc_macro2's line and
+/// column positions into byte offsets
+pub struct LineIndex<'a> {
+    source: &'a str,
+    bom: usize, // syn::parse_file skips a leading BOM; positions don't count it
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// `source` must be the text passed to `syn::parse_file`
+    pub fn new(source: &'a str) -> Self {
+        let (bom, source) = match source.strip_prefix('\u{feff}') {
+            Some(rest) => (source.len() - rest.len(), rest),
+            None => (0, source),
+        };
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            bom,
+            starts,
+        }
+    }
+
+    /// Byte offset into the original source. Positions past the end clamp
+    /// to it.
+    pub fn offset(&self, position: proc_macro2::LineColumn) -> usize {
+        // proc_macro2 lines are 1-based; columns count chars, not bytes
+        let offset = match self.starts.get(position.line.wrapping_sub(1)) {
+            Some(&start) => self.source[start..]
+                .char_indices()
+                .nth(position.column)
+                .map_or(self.source.len(), |(i, _)| start + i),
+            None => self.source.len(),
+        };
+        self.bom + offset
+    }
+}
+
+/// Functions which take a `WindowSystem` argument. `ast` is the source as
+/// written; attribute macros such as `closure_attr::with_closure` haven't
+/// expanded, so `#[closure(...)]` closures appear as event callbacks.
+pub fn get_functions(ast: &syn::File) -> Result<Vec<Function>, CodexError> {
     let mut functions = Vec::new();
     for item in ast.items.iter() {
         if let syn::Item::Fn(f) = item {
             if let Some(ws_fn) = Function::new(f) {
-                println!("{} {}", ws_fn.ws_type, ws_fn.ws_arg);
                 functions.push(ws_fn);
             }
         }
     }
     Ok(functions)
 }
+
+/// Parse `source` and describe each function which builds windows, i.e.
+/// `get_functions` followed by `serializable::Function::new`
+pub fn analyze(source: &str) -> Result<Vec<serializable::Function>, CodexError> {
+    let ast = syn::parse_file(source)?;
+    let index = LineIndex::new(source);
+    Ok(get_functions(&ast)?
+        .iter()
+        .map(|f| serializable::Function::new(f, &index))
+        .collect())
+}
+
+/// Regenerate the statements of `f` in order. Names and arguments
+/// are parsed back from their source text, which fails if they were edited
+/// into invalid Rust.
+pub fn to_tokens(f: &serializable::Function) -> syn::Result<TokenStream> {
+    let mut tokens = TokenStream::new();
+    for item in &f.items {
+        tokens.extend(item_to_tokens(item)?);
+    }
+    Ok(tokens)
+}
+
+fn item_to_tokens(item: &serializable::Item) -> syn::Result<TokenStream> {
+    use serializable::Item;
+    Ok(match item {
+        Item::Definition(d) => {
+            let name: syn::Ident = syn::parse_str(&d.name)?;
+            let parent: syn::Ident = syn::parse_str(&d.parent)?;
+            let create = method_to_tokens(&d.create)?;
+            let methods = methods_to_tokens(&d.methods)?;
+            let mutability = d.mutable.then(<syn::Token![mut]>::default);
+            quote!(let #mutability #name = #parent #create #methods;)
+        }
+        Item::Redefinition(r) => {
+            let name: syn::Ident = syn::parse_str(&r.name)?;
+            let methods = methods_to_tokens(&r.methods)?;
+            let mutability = r.mutable.then(<syn::Token![mut]>::default);
+            quote!(let #mutability #name = #name #methods;)
+        }
+        Item::Use(u) => {
+            let name: syn::Ident = syn::parse_str(&u.name)?;
+            let methods = methods_to_tokens(&u.methods)?;
+            let semicolon = u.semicolon.then(<syn::Token![;]>::default);
+            quote!(#name #methods #semicolon)
+        }
+        Item::Opaque(o) => {
+            // Parse within a block so a trailing expression needs no semicolon
+            let block: syn::Block = syn::parse_str(&format!("{{{}}}", o.text))?;
+            let stmts = block.stmts;
+            quote!(#(#stmts)*)
+        }
+    })
+}
+
+fn methods_to_tokens(methods: &[serializable::Method]) -> syn::Result<TokenStream> {
+    let mut tokens = TokenStream::new();
+    for method in methods {
+        tokens.extend(method_to_tokens(method)?);
+    }
+    Ok(tokens)
+}
+
+// .a(args)?, .a(args).unwrap(), etc.
+fn method_to_tokens(method: &serializable::Method) -> syn::Result<TokenStream> {
+    let name: syn::Ident = syn::parse_str(&method.name)?;
+    let args = method
+        .args
+        .iter()
+        .map(|arg| syn::parse_str::<syn::Expr>(&arg.value))
+        .collect::<syn::Result<Vec<_>>>()?;
+    let fallible: TokenStream = match &method.fallible {
+        Some(fallible) => syn::parse_str(fallible)?,
+        None => TokenStream::new(),
+    };
+    Ok(quote!(.#name(#(#args),*) #fallible))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEMO: &str = include_str!("../../trywin/src/main.rs");
+
+    fn demo_make(ast: &syn::File) -> &syn::ItemFn {
+        ast.items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(f) if f.sig.ident == "make" => Some(f),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    // Argument lists don't record a trailing comma
+    struct NoTrailingCommas;
+
+    impl syn::visit_mut::VisitMut for NoTrailingCommas {
+        fn visit_expr_method_call_mut(&mut self, call: &mut syn::ExprMethodCall) {
+            call.args.pop_punct();
+            syn::visit_mut::visit_expr_method_call_mut(self, call);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let ast = syn::parse_file(DEMO).unwrap();
+        let json = serde_json::to_string(&analyze(DEMO).unwrap()).unwrap();
+        let functions: Vec<serializable::Function> = serde_json::from_str(&json).unwrap();
+        let make = functions.iter().find(|f| f.name == "make").unwrap();
+
+        let tokens = to_tokens(make).unwrap();
+        let block: syn::Block = syn::parse2(quote!({ #tokens })).unwrap();
+        let mut expected = demo_make(&ast).block.clone();
+        syn::visit_mut::VisitMut::visit_block_mut(&mut NoTrailingCommas, &mut expected);
+        assert_eq!(block.stmts, expected.stmts);
+
+        // `let mut` and a tail expression survive too
+        let source = "fn f<WS: trywin::WindowSystem>(ws: WS) -> Result<WS::Window, WS::Error> {
+                 let mut window = ws.new_main()?;
+                 let mut window = window.title(\"x\")?;
+                 window.visible(true)
+             }";
+        let functions = analyze(source).unwrap();
+        let items = &functions[0].items;
+        assert_eq!(items.len(), 3);
+        assert!(matches!(&items[0], serializable::Item::Definition(d) if d.mutable));
+        assert!(matches!(&items[1], serializable::Item::Redefinition(r) if r.mutable));
+        assert!(matches!(&items[2], serializable::Item::Use(u) if !u.semicolon));
+        let tokens = to_tokens(&functions[0]).unwrap();
+        let block: syn::Block = syn::parse2(quote!({ #tokens })).unwrap();
+        let syn::Item::Fn(expected) = &syn::parse_file(source).unwrap().items[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(block.stmts, expected.block.stmts);
+    }
+
+    #[test]
+    fn opaque_in_order() {
+        let source = r#"
+            fn f<WS: trywin::WindowSystem>(ws: WS) {
+                let window = ws.new_main()?;
+                println!("created");
+                for i in 0..3 {
+                    window.text("x")?;
+                }
+                let count = 3;
+                window.visible(true)?;
+            }
+        "#;
+        let functions = analyze(source).unwrap();
+        let items: Vec<_> = functions[0]
+            .items
+            .iter()
+            .map(|item| match item {
+                serializable::Item::Definition(d) => format!("definition {}", d.name),
+                serializable::Item::Use(u) => format!("use {}", u.name),
+                serializable::Item::Opaque(o) => o.text.clone(),
+                serializable::Item::Redefinition(r) => format!("redefinition {}", r.name),
+            })
+            .collect();
+        assert_eq!(
+            items,
+            [
+                "definition window",
+                r#"println!("created");"#,
+                "for i in 0..3 {\n                    window.text(\"x\")?;\n                }",
+                "let count = 3;",
+                "use window",
+            ]
+        );
+    }
+
+    // First argument of `method` in the definition of `name`, sliced out of
+    // `source` by byte offset
+    fn arg_text<'a>(
+        source: &'a str,
+        functions: &[serializable::Function],
+        name: &str,
+        method: &str,
+    ) -> &'a str {
+        let span = functions
+            .iter()
+            .flat_map(|f| &f.items)
+            .find_map(|item| match item {
+                serializable::Item::Definition(d) if d.name == name => {
+                    d.methods.iter().find(|m| m.name == method)
+                }
+                _ => None,
+            })
+            .map(|m| &m.args[0].span)
+            .unwrap();
+        &source[span.start_byte..span.end_byte]
+    }
+
+    #[test]
+    fn byte_offsets() {
+        let functions = analyze(DEMO).unwrap();
+        assert_eq!(
+            arg_text(DEMO, &functions, "window", "title"),
+            r#""Hello, world!""#
+        );
+        assert_eq!(
+            arg_text(DEMO, &functions, "button2", "bounds"),
+            "Some((100, 100))"
+        );
+
+        // The BOM shifts every offset; columns count chars, not bytes
+        let source = "\u{feff}fn f<WS: trywin::WindowSystem>(ws: WS) {\n    \
+                      let w = ws.new_main()?.text(\"héllo\")?.title(\"wörld\")?;\n}\n";
+        let functions = analyze(source).unwrap();
+        assert_eq!(arg_text(source, &functions, "w", "text"), r#""héllo""#);
+        assert_eq!(arg_text(source, &functions, "w", "title"), r#""wörld""#);
+
+        let index = LineIndex::new(source);
+        let at = |line, column| index.offset(proc_macro2::LineColumn { line, column });
+        assert_eq!(at(1, 0), 3);
+        assert_eq!(at(2, 0), source.find("    let").unwrap());
+        assert_eq!(at(3, 1), source.len() - 1);
+        assert_eq!(at(9, 0), source.len());
+    }
+
+    #[test]
+    fn analyze_inline() {
+        let functions = analyze(
+            "fn not_ui() {}
+             fn ui<WS: ::trywin::WindowSystem>(ws: &WS) {
+                 let main = ws.new_main()?.text(\"hi\")?;
+             }",
+        )
+        .unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name, "ui");
+        let [serializable::Item::Definition(main)] = &functions[0].items[..] else {
+            panic!("expected one definition");
+        };
+        assert_eq!((main.name.as_str(), main.parent.as_str()), ("main", "ws"));
+
+        assert!(matches!(analyze("fn {"), Err(CodexError::Parse(_))));
+    }
+
+    #[test]
+    fn destroy_is_lifecycle() {
+        let ast = syn::parse_file(
+            "fn ui<WS: ::trywin::WindowSystem>(ws: &WS) {
+                 let main = ws.new_main()?;
+                 main.destroy()?;
+             }",
+        )
+        .unwrap();
+        let functions = get_functions(&ast).unwrap();
+        let [_, Item::Use(destroy)] = &functions[0].items[..] else {
+            panic!("expected a definition and a use");
+        };
+        assert_eq!(destroy.ident, "main");
+        assert_eq!(destroy.methods[0].ident, "destroy");
+        assert_eq!(destroy.methods[0].method_type, MethodType::Lifecycle);
+        assert!(destroy.methods[0].attr_kind.is_none());
+    }
+
+    #[test]
+    fn event_closures() {
+        let functions = analyze(DEMO).unwrap();
+        let events: Vec<_> = functions
+            .iter()
+            .flat_map(|f| &f.items)
+            .filter_map(|item| match item {
+                serializable::Item::Use(u) if u.methods[0].method_type == "event" => {
+                    Some(&u.methods[0])
+                }
+                _ => None,
+            })
+            .collect();
+        let [on_close, on_destroy] = &events[..] else {
+            panic!("expected on_close and on_destroy");
+        };
+
+        assert_eq!(on_close.name, "on_close");
+        let body = on_close.event_body.as_deref().unwrap();
+        assert!(body.starts_with('{') && body.ends_with('}'));
+        assert!(body.contains("CloseAction::Close"));
+        let captures: Vec<_> = on_close
+            .event_captures
+            .iter()
+            .map(|c| (c.mode.as_str(), c.name.as_str()))
+            .collect();
+        assert_eq!(captures, [("weak", "edit")]);
+
+        assert_eq!(on_destroy.name, "on_destroy");
+        assert_eq!(
+            on_destroy.event_body.as_deref(),
+            Some(r#"println!("Edit text: {:?}", edit.get_text().unwrap())"#)
+        );
+        let captures: Vec<_> = on_destroy
+            .event_captures
+            .iter()
+            .map(|c| (c.mode.as_str(), c.name.as_str()))
+            .collect();
+        assert_eq!(captures, [("weak", "edit")]);
+    }
+
+    #[test]
+    fn with_closure_demo() {
+        let ast = syn::parse_file(DEMO).unwrap();
+        let make = demo_make(&ast);
+        assert!(make.attrs[0].path().segments.last().unwrap().ident == "with_closure");
+
+        let functions = get_functions(&ast).unwrap();
+        let names: Vec<_> = functions.iter().map(|f| f.ident.to_string()).collect();
+        assert_eq!(names, ["make", "_make2"]);
+
+        // The closure statements are recognized; only the non-builder
+        // statements are opaque
+        let opaque: Vec<_> = functions[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Opaque(stmt) => Some(quote!(#stmt).to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(opaque.len(), 4);
+        assert_eq!(opaque[0], "use :: trywin :: * ;");
+        assert!(opaque[1].starts_with("# [allow (dead_code)] struct All"));
+        assert!(opaque[2].starts_with("# [allow (unused_variables)] let all"));
+        assert_eq!(opaque[3], "Ok (window)");
+
+        let events: Vec<_> = functions[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Use(u) => Some(&u.methods[0]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(events.len(), 2);
+        for event in events {
+            assert_eq!(event.method_type, MethodType::Event);
+            assert!(event.closure.is_some_and(has_closure_attr));
+        }
+    }
+
+    #[test]
+    fn demo_root() {
+        let functions = analyze(DEMO).unwrap();
+        let definitions: Vec<_> = functions[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                serializable::Item::Definition(d) => {
+                    Some((d.name.as_str(), d.parent.as_str(), d.is_root))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            definitions,
+            [
+                ("window", "ws", true),
+                ("color1", "window", false),
+                ("color2", "window", false),
+                ("color3", "window", false),
+                ("button1", "window", false),
+                ("button2", "window", false),
+                ("edit", "window", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn demo_tree() {
+        let functions = analyze(DEMO).unwrap();
+        let root = functions[0].build_tree().unwrap();
+        assert_eq!(root.name, "window");
+        assert_eq!(root.create.name, "new_main");
+
+        // Uses of window after its definition are merged in
+        let methods: Vec<_> = root.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(
+            methods,
+            ["bounds", "title", "background", "on_close", "on_destroy"]
+        );
+
+        let children: Vec<_> = root.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            children,
+            ["color1", "color2", "color3", "button1", "button2", "edit"]
+        );
+        assert!(root.children.iter().all(|c| c.children.is_empty()));
+    }
+
+    // Keeps AttrKind in step with trywin::Window
+    #[test]
+    fn attr_kinds_cover_builders() {
+        let ast = syn::parse_file(include_str!("../../trywin/src/lib.rs")).unwrap();
+        let window = ast
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Trait(t) if t.ident == "Window" => Some(t),
+                _ => None,
+            })
+            .unwrap();
+        let mut builders = 0;
+        for item in &window.items {
+            let syn::TraitItem::Fn(f) = item else {
+                continue;
+            };
+            let by_value = matches!(
+                f.sig.inputs.first(),
+                Some(syn::FnArg::Receiver(r)) if r.reference.is_none()
+            );
+            if !by_value || MethodType::from_ident(&f.sig.ident) != MethodType::Attr {
+                continue;
+            }
+            let kind = AttrKind::from_ident(&f.sig.ident);
+            assert_eq!(kind.map(AttrKind::name), Some(&*f.sig.ident.to_string()));
+            builders += 1;
+        }
+        assert_eq!(builders, 24);
+    }
+}
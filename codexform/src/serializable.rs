@@ -1,7 +1,10 @@
 use quote::quote;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use syn::spanned::Spanned;
 
+use crate::LineIndex;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Position {
     /// 1-based line number
@@ -27,22 +30,29 @@ pub struct Span {
 
     /// Exclusive
     pub end: Position,
+
+    /// Byte offset of `start` in the source, for slicing it directly
+    pub start_byte: usize,
+
+    /// Byte offset of `end`; exclusive
+    pub end_byte: usize,
 }
 
-impl From<&proc_macro2::Span> for Span {
-    fn from(span: &proc_macro2::Span) -> Self {
-        Self {
-            start: span.start().into(),
-            end: span.end().into(),
-        }
+impl Span {
+    pub fn new(span: proc_macro2::Span, index: &LineIndex) -> Self {
+        Self::from_range(span.start(), span.end(), index)
     }
-}
 
-impl From<proc_macro2::Span> for Span {
-    fn from(span: proc_macro2::Span) -> Self {
+    fn from_range(
+        start: proc_macro2::LineColumn,
+        end: proc_macro2::LineColumn,
+        index: &LineIndex,
+    ) -> Self {
         Self {
-            start: span.start().into(),
-            end: span.end().into(),
+            start: start.into(),
+            end: end.into(),
+            start_byte: index.offset(start),
+            end_byte: index.offset(end),
         }
     }
 }
@@ -53,27 +63,78 @@ pub struct Arg {
     pub value: String,
 }
 
+/// A variable captured by an event closure through `#[closure(...)]`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Capture {
+    /// closure_attr's capture mode, e.g. "clone" or "weak"
+    pub mode: String,
+    pub name: String,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Method {
     pub span: Span,
     pub name: String,
+
+    /// "create", "attr", "event", or "lifecycle", e.g. destroy or visible
+    pub method_type: String,
+
+    /// Known trywin attribute, e.g. "text". None for creation, events,
+    /// lifecycle methods, and unknown attributes.
+    pub attr_kind: Option<String>,
     pub args: Vec<Arg>,
+
+    /// How the Result is unwrapped: "?", ".unwrap()", ".expect(...)", or
+    /// None if the method isn't fallible.
+    pub fallible: Option<String>,
+
+    /// Body of an event method's closure argument, verbatim. None for other
+    /// methods and for events given anything but a lone closure.
+    pub event_body: Option<String>,
+
+    /// Variables the event closure captures via closure_attr. Plain
+    /// closures capture implicitly, so this is empty for them.
+    pub event_captures: Vec<Capture>,
 }
 
-impl<'a> From<&crate::Method<'a>> for Method {
-    fn from(m: &crate::Method) -> Self {
+impl Method {
+    fn new(m: &crate::Method, index: &LineIndex) -> Self {
         Self {
-            span: Span {
-                start: m.dot.span.start().into(),
-                end: m.question.span.end().into(),
-            },
+            span: Span::from_range(m.dot.span.start(), m.end().end(), index),
             name: m.ident.to_string(),
+            method_type: m.method_type.name().to_string(),
+            attr_kind: m.attr_kind.map(|k| k.name().to_string()),
             args: m
                 .args
                 .iter()
                 .map(|arg| Arg {
-                    span: arg.span().into(),
-                    value: quote!(arg).to_string(),
+                    span: Span::new(arg.span(), index),
+                    value: quote!(#arg).to_string(),
+                })
+                .collect(),
+            fallible: match m.fallible {
+                crate::Fallible::No => None,
+                crate::Fallible::Try(question) => Some(quote!(#question).to_string()),
+                crate::Fallible::Unwrap(call) => {
+                    let method = &call.method;
+                    let args = &call.args;
+                    Some(quote!(.#method(#args)).to_string())
+                }
+            },
+            event_body: m.closure.map(|closure| {
+                let body = &closure.body;
+                body.span()
+                    .source_text()
+                    .unwrap_or_else(|| quote!(#body).to_string())
+            }),
+            event_captures: m
+                .closure
+                .map(crate::closure_captures)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|capture| Capture {
+                    mode: capture.mode,
+                    name: capture.ident.to_string(),
                 })
                 .collect(),
         }
@@ -84,19 +145,30 @@ impl<'a> From<&crate::Method<'a>> for Method {
 pub struct Definition {
     pub span: Span,
     pub name: String,
+
+    /// Declared with `let mut`
+    pub mutable: bool,
+
+    /// Parent window, or the WindowSystem argument if `is_root`
     pub parent: String,
+
+    /// Created by the WindowSystem, e.g. with new_main, rather than as a
+    /// child of another window
+    pub is_root: bool,
     pub create: Method,
     pub methods: Vec<Method>,
 }
 
-impl<'a> From<&crate::Definition<'a>> for Definition {
-    fn from(d: &crate::Definition<'a>) -> Self {
+impl Definition {
+    fn new(d: &crate::Definition, index: &LineIndex) -> Self {
         Self {
-            span: d.statement.span().into(),
+            span: Span::new(d.statement.span(), index),
             name: d.ident.to_string(),
+            mutable: d.mutable,
             parent: d.parent.to_string(),
-            create: (&d.create).into(),
-            methods: d.methods.iter().map(Method::from).collect(),
+            is_root: d.is_root,
+            create: Method::new(&d.create, index),
+            methods: d.methods.iter().map(|m| Method::new(m, index)).collect(),
         }
     }
 }
@@ -105,15 +177,19 @@ impl<'a> From<&crate::Definition<'a>> for Definition {
 pub struct Redefinition {
     pub span: Span,
     pub name: String,
+
+    /// Declared with `let mut`
+    pub mutable: bool,
     pub methods: Vec<Method>,
 }
 
-impl<'a> From<&crate::Redefinition<'a>> for Redefinition {
-    fn from(r: &crate::Redefinition<'a>) -> Self {
+impl Redefinition {
+    fn new(r: &crate::Redefinition, index: &LineIndex) -> Self {
         Self {
-            span: r.statement.span().into(),
+            span: Span::new(r.statement.span(), index),
             name: r.ident.to_string(),
-            methods: r.methods.iter().map(Method::from).collect(),
+            mutable: r.mutable,
+            methods: r.methods.iter().map(|m| Method::new(m, index)).collect(),
         }
     }
 }
@@ -122,32 +198,61 @@ impl<'a> From<&crate::Redefinition<'a>> for Redefinition {
 pub struct Use {
     pub span: Span,
     pub name: String,
+
+    /// False for a function's tail expression, whose value is returned
+    pub semicolon: bool,
     pub methods: Vec<Method>,
 }
 
-impl<'a> From<&crate::Use<'a>> for Use {
-    fn from(u: &crate::Use<'a>) -> Self {
+impl Use {
+    fn new(u: &crate::Use, index: &LineIndex) -> Self {
         Self {
-            span: u.statement.span().into(),
+            span: Span::new(u.statement.span(), index),
             name: u.ident.to_string(),
-            methods: u.methods.iter().map(Method::from).collect(),
+            semicolon: u.semicolon,
+            methods: u.methods.iter().map(|m| Method::new(m, index)).collect(),
         }
     }
 }
 
+/// A statement which isn't part of a builder chain
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Opaque {
+    pub span: Span,
+
+    /// Verbatim source
+    pub text: String,
+}
+
+impl Opaque {
+    fn new(stmt: &syn::Stmt, index: &LineIndex) -> Self {
+        let span = stmt.span();
+        Self {
+            span: Span::new(span, index),
+            text: span
+                .source_text()
+                .unwrap_or_else(|| quote!(#stmt).to_string()),
+        }
+    }
+}
+
+// Items are built once per statement; boxing would only burden consumers
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Item {
     Definition(Definition),
     Redefinition(Redefinition),
     Use(Use),
+    Opaque(Opaque),
 }
 
-impl<'a> From<&crate::Item<'a>> for Item {
-    fn from(item: &crate::Item<'a>) -> Self {
+impl Item {
+    fn new(item: &crate::Item, index: &LineIndex) -> Self {
         match item {
-            crate::Item::Definition(item) => Self::Definition(item.into()),
-            crate::Item::Redefinition(item) => Self::Redefinition(item.into()),
-            crate::Item::Use(item) => Self::Use(item.into()),
+            crate::Item::Definition(item) => Self::Definition(Definition::new(item, index)),
+            crate::Item::Redefinition(item) => Self::Redefinition(Redefinition::new(item, index)),
+            crate::Item::Use(item) => Self::Use(Use::new(item, index)),
+            crate::Item::Opaque(stmt) => Self::Opaque(Opaque::new(stmt, index)),
         }
     }
 }
@@ -159,20 +264,74 @@ pub struct Function {
     pub items: Vec<Item>,
 }
 
-impl<'a> From<&crate::Function<'a>> for Function {
-    fn from(f: &crate::Function<'a>) -> Self {
+impl Function {
+    /// `index` must be built from the source `f` was parsed from
+    pub fn new(f: &crate::Function, index: &LineIndex) -> Self {
         Self {
             name: f.ident.to_string(),
-            span: Span {
-                start: f.f.sig.span().start().into(),
-                end: f.f.block.span().end().into(),
-            },
-            items: f
-                .items
-                .iter()
-                .flat_map(|(_, i)| i)
-                .map(Item::from)
-                .collect(),
+            span: Span::from_range(f.f.sig.span().start(), f.f.block.span().end(), index),
+            items: f.items.iter().map(|item| Item::new(item, index)).collect(),
+        }
+    }
+}
+
+/// A window and the windows created from it; see `Function::build_tree`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WindowNode {
+    pub name: String,
+    pub create: Method,
+
+    /// Methods from the definition, then from later redefinitions and uses
+    /// of the same variable, in source order
+    pub methods: Vec<Method>,
+
+    /// In source order
+    pub children: Vec<WindowNode>,
+}
+
+impl Function {
+    /// Nest the definitions by parent. Returns the first root, normally the
+    /// `new_main` window, or None if there isn't one. Definitions whose
+    /// parent isn't a window defined earlier are left out. Reusing a name
+    /// starts a new window; later statements apply to it.
+    pub fn build_tree(&self) -> Option<WindowNode> {
+        let mut nodes: Vec<(WindowNode, Option<usize>)> = Vec::new(); // With parent
+        let mut by_name = HashMap::new();
+        for item in &self.items {
+            let (name, methods) = match item {
+                Item::Definition(d) => {
+                    let parent = match (d.is_root, by_name.get(d.parent.as_str())) {
+                        (true, _) => None,
+                        (false, Some(&parent)) => Some(parent),
+                        (false, None) => continue,
+                    };
+                    let node = WindowNode {
+                        name: d.name.clone(),
+                        create: d.create.clone(),
+                        methods: d.methods.clone(),
+                        children: Vec::new(),
+                    };
+                    by_name.insert(d.name.as_str(), nodes.len());
+                    nodes.push((node, parent));
+                    continue;
+                }
+                Item::Redefinition(r) => (&r.name, &r.methods),
+                Item::Use(u) => (&u.name, &u.methods),
+                Item::Opaque(_) => continue,
+            };
+            if let Some(&i) = by_name.get(name.as_str()) {
+                nodes[i].0.methods.extend(methods.iter().cloned());
+            }
+        }
+
+        // Parents precede their children, so attach from the end
+        let mut root = None;
+        while let Some((node, parent)) = nodes.pop() {
+            match parent {
+                Some(parent) => nodes[parent].0.children.insert(0, node),
+                None => root = Some(node),
+            }
         }
+        root
     }
 }
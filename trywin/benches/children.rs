@@ -0,0 +1,45 @@
+// Time creating 500 controls one at a time vs. with new_children.
+//
+//     cargo bench --bench children
+
+use std::time::{Duration, Instant};
+use trywin::{comm_ctrl::System, ChildSpec, ChildType, Window, WindowSystem};
+
+const COUNT: usize = 500;
+const ROUNDS: usize = 5;
+
+fn time<F: FnMut(&<System as WindowSystem>::Window)>(system: &System, mut f: F) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let window = system.new_main().unwrap();
+        let start = Instant::now();
+        f(&window);
+        best = best.min(start.elapsed());
+        window.destroy().unwrap();
+    }
+    best
+}
+
+fn main() {
+    let system = System::new();
+
+    // Registers classes and initializes common controls up front, so
+    // neither measurement pays for it
+    let warm_up = system.new_main().unwrap();
+    warm_up.new_child(ChildType::Button).unwrap();
+    warm_up.destroy().unwrap();
+
+    let one_at_a_time = time(&system, |window| {
+        for _ in 0..COUNT {
+            window.new_child(ChildType::Button).unwrap();
+        }
+    });
+    let specs = vec![ChildSpec::new(ChildType::Button); COUNT];
+    let batched = time(&system, |window| {
+        window.new_children(&specs).unwrap();
+    });
+
+    println!("{} controls, best of {} rounds", COUNT, ROUNDS);
+    println!("  new_child:    {:?}", one_at_a_time);
+    println!("  new_children: {:?}", batched);
+}
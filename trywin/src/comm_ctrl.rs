@@ -1,14 +1,70 @@
 #![allow(clippy::too_many_arguments)]
 
 use closure_attr::{Downgrade, Upgrade};
-use std::{cell::RefCell, mem::size_of, rc::Rc, result::Result};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    ffi::c_void,
+    mem::size_of,
+    path::PathBuf,
+    rc::{Rc, Weak},
+    result::Result,
+    time::Duration,
+};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Waker},
+};
+#[cfg(feature = "async")]
+use windows::Win32::System::Threading::INFINITE;
 use windows::{
     core,
     Win32::Storage::Xps::*,
-    Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
+    Win32::{
+        Foundation::*,
+        Graphics::{
+            Dwm::{
+                DwmSetWindowAttribute, DWMWA_CLOAK, DWMWA_USE_IMMERSIVE_DARK_MODE,
+                DWMWINDOWATTRIBUTE,
+            },
+            Gdi::*,
+        },
+        System::{
+            Com::{
+                CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+                COINIT_APARTMENTTHREADED, COINIT_DISABLE_OLE1DDE,
+            },
+            SystemInformation::OSVERSIONINFOW,
+            Threading::GetCurrentThreadId,
+        },
+        UI::{
+            Accessibility::{AutomationId_Property_GUID, CLSID_AccPropServices, IAccPropServices},
+            Controls::{
+                SetScrollInfo, NMHDR, NMTREEVIEWW, NMUPDOWN, TVN_DELETEITEMW, TVN_SELCHANGEDW,
+                TVS_HASBUTTONS, TVS_HASLINES, TVS_LINESATROOT, TVS_SHOWSELALWAYS, UDM_GETRANGE32,
+                UDN_DELTAPOS, UDS_ALIGNRIGHT, UDS_ARROWKEYS, UDS_SETBUDDYINT,
+            },
+            Input::KeyboardAndMouse::{GetActiveWindow, GetCapture, ReleaseCapture, SetCapture},
+            Shell::{DragAcceptFiles, HDROP},
+            WindowsAndMessaging::*,
+        },
+    },
+};
+
+use crate::{
+    Accelerator, Bitmap, ChildSpec, ChildType, CloseAction, Color, Cursor, CustomOptions,
+    EditOptions, MessageButtons, MessageResult, MouseEvent, PixelOrder, Rect, ScrollBar, ScrollPos,
+    ScrollRequest, TreeItem, WindowChrome, WindowExStyle, WindowState, WindowStyle, WindowSystem,
 };
 
-use crate::{Bitmap, ChildType, Color, EditOptions, WindowSystem};
+pub mod clipboard;
+pub mod controls;
+pub mod dialog;
+pub mod drawing;
+use controls::*;
 
 pub mod object_wrappers;
 use object_wrappers::{Error, *};
@@ -16,15 +72,346 @@ use object_wrappers::{Error, *};
 pub mod wndproc_wrappers;
 use wndproc_wrappers::*;
 
-#[derive(Clone, Debug, Default)]
-pub struct System;
+/// Windows are tied to the thread which creates them. System remembers the
+/// thread it was created on and refuses to create windows or run the event
+/// loop anywhere else.
+#[derive(Clone, Debug)]
+pub struct System {
+    thread: u32,
+}
 
 impl System {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            thread: unsafe { GetCurrentThreadId() },
+        }
+    }
+
+    /// Like [System::new], but also initializes COM on this thread as a
+    /// single-threaded apartment. File dialogs, OLE drag-drop, and similar
+    /// features need COM, and OLE drag-drop requires the UI thread to be STA.
+    ///
+    /// Calling this more than once is fine; COM counts initializations. Each
+    /// one is balanced by `CoUninitialize` at thread exit, so COM stays
+    /// available across any number of event loops. Fails if COM was already
+    /// initialized on this thread as multithreaded.
+    pub fn with_com() -> Result<Self, Error> {
+        // S_FALSE (already initialized) is a success and still needs balancing
+        unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE)? };
+        COM.with(|c| c.borrow_mut().push(ComGuard));
+        Ok(Self::new())
+    }
+
+    /// Font sent to each window created afterwards on this thread. Without
+    /// one, windows get the system message font (e.g. Segoe UI) rather than
+    /// the archaic default. Windows keep their font alive, so replacing it
+    /// doesn't affect existing windows.
+    pub fn set_default_font(&self, family: &str, point_size: i32) -> Result<(), Error> {
+        self.check_thread()?;
+        let font = Rc::new(HFont::new(family, point_size)?);
+        DEFAULT_FONT.with(|f| *f.borrow_mut() = Some(font));
+        Ok(())
+    }
+
+    /// Draw common controls created afterwards on this thread with visual
+    /// styles (comctl32 v6), without the application needing a manifest.
+    /// Controls created earlier keep the classic look. Applications which
+    /// already embed a comctl32 v6 manifest don't need this.
+    pub fn enable_visual_styles(&self) -> Result<(), Error> {
+        self.check_thread()?;
+        let ctx = Rc::new(ActCtx::common_controls_v6()?);
+        VISUAL_STYLES.with(|v| *v.borrow_mut() = Some(ctx));
+        Ok(())
+    }
+
+    /// Capture the virtual screen, spanning every monitor
+    pub fn capture_screen(&self) -> Result<Bitmap, Error> {
+        let (x, y, w, h) = unsafe {
+            (
+                GetSystemMetrics(SM_XVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN),
+                GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                GetSystemMetrics(SM_CYVIRTUALSCREEN),
+            )
+        };
+        capture_screen_rect(x, y, w, h)
+    }
+
+    /// Capture one monitor. Indexes follow the system's enumeration order,
+    /// which doesn't necessarily put the primary monitor first.
+    pub fn capture_monitor(&self, index: usize) -> Result<Bitmap, Error> {
+        let (x, y, w, h) = *monitor_rects().get(index).ok_or(Error::NoMonitor(index))?;
+        capture_screen_rect(x, y, w, h)
+    }
+
+    /// Wrap a window created outside this library, e.g. a game engine's
+    /// window, so callbacks and builders work on it. Our window procedure
+    /// is attached as a subclass in front of the window's own.
+    ///
+    /// The result doesn't own the window: dropping the last handle detaches
+    /// our window procedure and leaves the window alive. `destroy` still
+    /// destroys it. Adopting a window this library created returns the
+    /// existing handle.
+    ///
+    /// # Safety
+    ///
+    /// * `handle` must be an `HWND`. It may be destroyed at any time; the
+    ///   handle then reports it as destroyed like any other window.
+    /// * The window's own procedure must tolerate messages this library
+    ///   sends and handles (e.g. `WM_SETTEXT`, custom background erases).
+    /// * Nothing else may remove or reorder subclass 0 of our subclass
+    ///   procedure on the window.
+    pub unsafe fn adopt(&self, handle: *mut c_void) -> Result<Window, Error> {
+        self.check_thread()?;
+        let hwnd = HWND(handle as isize);
+        if !IsWindow(hwnd).as_bool() || GetWindowThreadProcessId(hwnd, None) != self.thread {
+            return Err(Error::NotAdoptable);
+        }
+        if let Some(window) = find_window(hwnd) {
+            return Ok(window);
+        }
+        WindowImpl::adopt(hwnd)
+    }
+
+    /// This executable's module handle (`HINSTANCE`), e.g. for loading
+    /// resources or registering window classes
+    pub fn instance(&self) -> Result<*mut c_void, Error> {
+        Ok(module_instance()?.0 as *mut c_void)
+    }
+
+    /// The running Windows version. Unlike `GetVersionEx`, this isn't
+    /// capped at 6.2 for applications without a compatibility manifest.
+    pub fn os_version(&self) -> OsVersion {
+        os_version()
+    }
+
+    /// Whether the running Windows version has `feature`
+    pub fn supports(&self, feature: Feature) -> bool {
+        os_version() >= feature.min_version()
+    }
+
+    /// Like [WindowSystem::event_loop], but also drives `future`, so the UI
+    /// thread can await I/O without a second thread. The future is polled
+    /// whenever its waker fires, which may happen on any thread; e.g. run
+    /// it inside a multithreaded tokio runtime's `Handle::enter` so tokio's
+    /// I/O resources work. Returns when the loop exits, even if the future
+    /// hasn't finished; the loop keeps running after it has.
+    #[cfg(feature = "async")]
+    pub fn run_forever_async<F: Future<Output = ()>>(&self, future: F) -> Result<(), Error> {
+        self.check_thread()?;
+        let event = Arc::new(WakeEvent::new()?);
+        let waker = Waker::from(event.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        let mut done = false;
+        event.set();
+        unsafe {
+            let handles = [event.raw()];
+            let mut msg = MSG::default();
+            loop {
+                let wait = MsgWaitForMultipleObjectsEx(
+                    Some(&handles),
+                    INFINITE,
+                    QS_ALLINPUT,
+                    MWMO_INPUTAVAILABLE,
+                );
+                if wait == WAIT_OBJECT_0 {
+                    if !done {
+                        done = future.as_mut().poll(&mut cx).is_ready();
+                    }
+                } else if wait.0 == WAIT_OBJECT_0.0 + handles.len() as u32 {
+                    while PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE).into() {
+                        if msg.message == WM_QUIT {
+                            return Ok(());
+                        }
+                        dispatch(&msg);
+                    }
+                } else {
+                    Err(core::Error::from_win32())?;
+                }
+            }
+        }
+    }
+
+    fn check_thread(&self) -> Result<(), Error> {
+        check_thread(self.thread)
+    }
+}
+
+fn os_version() -> OsVersion {
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+    // Safety: info is sized for RtlGetVersion, which always succeeds
+    unsafe { RtlGetVersion(&mut info) };
+    OsVersion {
+        major: info.dwMajorVersion,
+        minor: info.dwMinorVersion,
+        build: info.dwBuildNumber,
+    }
+}
+
+// Not in the windows crate's Win32 metadata
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlGetVersion(info: *mut OSVERSIONINFOW) -> NTSTATUS;
+}
+
+/// Windows version; compares in release order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OsVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub build: u32,
+}
+
+impl OsVersion {
+    pub const fn new(major: u32, minor: u32, build: u32) -> Self {
+        Self {
+            major,
+            minor,
+            build,
+        }
+    }
+}
+
+/// OS features which older Windows versions lack. See [System::supports].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Layered (`WS_EX_LAYERED`) child windows; Windows 8
+    LayeredChildWindows,
+    /// `PrintWindow` with `PW_RENDERFULLCONTENT`; Windows 8.1
+    RenderFullContent,
+    /// Dark title bars and controls; Windows 10 1809
+    DarkMode,
+}
+
+impl Feature {
+    pub fn min_version(self) -> OsVersion {
+        match self {
+            Feature::LayeredChildWindows => OsVersion::new(6, 2, 0),
+            Feature::RenderFullContent => OsVersion::new(6, 3, 0),
+            Feature::DarkMode => OsVersion::new(10, 0, 17763),
+        }
+    }
+}
+
+thread_local! {
+    // Font for new windows; see System::set_default_font
+    static DEFAULT_FONT: RefCell<Option<Rc<HFont>>> = Default::default();
+
+    // Font for new windows without a DEFAULT_FONT, and the LOGFONTW it was
+    // made from; see refresh_system_font
+    static SYSTEM_FONT: RefCell<Option<(LOGFONTW, Rc<HFont>)>> = Default::default();
+}
+
+thread_local! {
+    // See System::enable_visual_styles
+    static VISUAL_STYLES: RefCell<Option<Rc<ActCtx>>> = Default::default();
+}
+
+fn default_font() -> Option<Rc<HFont>> {
+    if let Some(font) = DEFAULT_FONT.with(|f| f.borrow().clone()) {
+        return Some(font);
+    }
+    SYSTEM_FONT.with(|f| {
+        let mut f = f.borrow_mut();
+        if f.is_none() {
+            *f = HFont::message_logfont()
+                .and_then(|lf| Ok((lf, Rc::new(HFont::indirect(&lf)?))))
+                .ok();
+        }
+        f.as_ref().map(|(_, font)| font.clone())
+    })
+}
+
+// Recreate SYSTEM_FONT if the user changed the message font, and move every
+// window on this thread which has the old one to the new one. Each top-level
+// window gets the notification; only the first sees a change.
+fn refresh_system_font() {
+    let Ok(lf) = HFont::message_logfont() else {
+        return;
+    };
+    let changed = SYSTEM_FONT.with(|f| {
+        let mut f = f.borrow_mut();
+        // Nothing to refresh if it's unchanged or no window has used it yet
+        let stale = f.as_ref().is_some_and(|(cached, _)| *cached != lf);
+        if !stale {
+            return None;
+        }
+        let font = Rc::new(HFont::indirect(&lf).ok()?);
+        let (_, old) = f.replace((lf, font.clone()))?;
+        Some((old, font))
+    });
+    let Some((old, new)) = changed else {
+        return;
+    };
+    let windows: Vec<Window> =
+        REGISTRY.with(|r| r.borrow().values().filter_map(Weak::upgrade).collect());
+    for window in windows {
+        let has_old =
+            matches!(&*window.callbacks.font.borrow(), Some(font) if Rc::ptr_eq(font, &old));
+        if !has_old {
+            continue;
+        }
+        // lparam TRUE redraws. Fails for windows destroyed since being
+        // collected; they keep the old font until dropped.
+        let sent = window.send_message(WM_SETFONT, WPARAM(unsafe { new.raw() }.0 as _), LPARAM(1));
+        if sent.is_ok() {
+            *window.callbacks.font.borrow_mut() = Some(new.clone());
+        }
+    }
+}
+
+thread_local! {
+    // Outstanding COM initializations made by System::with_com, balanced
+    // when the thread exits
+    static COM: RefCell<Vec<ComGuard>> = Default::default();
+}
+
+// Balances one successful CoInitializeEx
+struct ComGuard;
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe { CoUninitialize() };
+    }
+}
+
+// Window::automation_id's services, which keep COM initialized until they're
+// released, even if the window outlives the thread's other COM users
+struct Annotations {
+    services: IAccPropServices,
+    _com: Option<ComGuard>, // Declared last so it drops after services
+}
+
+impl Annotations {
+    fn new(services: IAccPropServices) -> Self {
+        // Fails with RPC_E_CHANGED_MODE in a multithreaded apartment, which
+        // whoever initialized it keeps alive
+        let com =
+            unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE) };
+        Self {
+            services,
+            _com: com.ok().map(|()| ComGuard),
+        }
     }
 }
 
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_thread(thread: u32) -> Result<(), Error> {
+    if unsafe { GetCurrentThreadId() } != thread {
+        return Err(Error::WrongThread);
+    }
+    Ok(())
+}
+
 impl Downgrade for System {
     type Target = Self;
     fn downgrade(this: &Self) -> Self::Target {
@@ -43,8 +430,14 @@ impl WindowSystem for System {
     type Error = Error;
     type Window = Window;
     type Child = Window;
+    type Button = Button;
+    type Edit = Edit;
+    type ListBox = ListBox;
+    type UpDown = UpDown;
+    type TreeView = TreeView;
 
     fn new_main(&self) -> Result<Self::Window, Error> {
+        self.check_thread()?;
         unsafe {
             WindowImpl::new(
                 WS_OVERLAPPEDWINDOW | WS_CLIPCHILDREN,
@@ -55,27 +448,134 @@ impl WindowSystem for System {
                 None,
                 None,
                 None,
+                None,
+            )
+        }
+    }
+
+    fn new_main_visible(&self) -> Result<Self::Window, Error> {
+        crate::Window::visible(self.new_main()?, true)
+    }
+
+    fn new_message_only(&self) -> Result<Self::Window, Error> {
+        self.check_thread()?;
+        unsafe {
+            WindowImpl::new(
+                Default::default(),
+                Default::default(),
+                HWND_MESSAGE,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
         }
     }
 
-    // TODO: keyboard, dialog, redirect control notifications
     fn event_loop(&self) -> Result<(), Error> {
+        self.check_thread()?;
         unsafe {
             let mut msg = MSG::default();
             while GetMessageW(&mut msg, HWND(0), 0, 0).into() {
-                DispatchMessageW(&msg);
+                dispatch(&msg);
             }
             Ok(())
         }
     }
 
+    fn run_with_timeout(&self, duration: Duration) -> Result<(), Error> {
+        // Timer ids are per-window and the sink is private, so this can't
+        // collide with user timers.
+        const TIMEOUT_TIMER_ID: usize = 1;
+
+        let sink = self.new_message_only()?;
+        sink.set_callback(
+            &sink.callbacks.on_message,
+            Box::new(|message, wparam, _| {
+                if message == WM_TIMER && wparam == TIMEOUT_TIMER_ID {
+                    unsafe { PostQuitMessage(0) };
+                    true
+                } else {
+                    false
+                }
+            }),
+        );
+        let elapse = duration.as_millis().min(USER_TIMER_MAXIMUM as u128) as u32;
+        unsafe {
+            if SetTimer(sink.hwnd(), TIMEOUT_TIMER_ID, elapse, None) == 0 {
+                Err(core::Error::from_win32())?;
+            }
+        }
+        let result = self.event_loop();
+        // Best effort; the loop's own result matters more, and the sink's
+        // destruction kills the timer anyway
+        let _ = unsafe { KillTimer(sink.hwnd(), TIMEOUT_TIMER_ID) };
+        result
+    }
+
     fn exit_loop(&self) -> Result<(), Error> {
         unsafe {
             PostQuitMessage(0);
             Ok(())
         }
     }
+
+    fn run(&self, main_window: &Window) -> Result<(), Error> {
+        main_window.check_live()?;
+        main_window.callbacks.options.borrow_mut().quit_on_destroy = true;
+        self.event_loop()
+    }
+
+    fn live_windows(&self) -> Vec<Window> {
+        REGISTRY.with(|r| {
+            let mut r = r.borrow_mut();
+            r.retain(|_, w| w.strong_count() > 0);
+            r.values()
+                .filter_map(Weak::upgrade)
+                .filter(|w| w.live())
+                .collect()
+        })
+    }
+
+    fn active_window(&self) -> Option<Window> {
+        find_window(unsafe { GetActiveWindow() })
+    }
+
+    fn message_box(
+        &self,
+        parent: Option<&Window>,
+        title: &str,
+        text: &str,
+        buttons: MessageButtons,
+    ) -> Result<MessageResult, Error> {
+        let owner = match parent {
+            Some(parent) => {
+                parent.check_live()?;
+                unsafe { parent.hwnd() }
+            }
+            None => HWND(0),
+        };
+        let style = match buttons {
+            MessageButtons::Ok => MB_OK,
+            MessageButtons::OkCancel => MB_OKCANCEL,
+            MessageButtons::YesNo => MB_YESNO,
+            MessageButtons::YesNoCancel => MB_YESNOCANCEL,
+            MessageButtons::RetryCancel => MB_RETRYCANCEL,
+        };
+        let text = WideZString::new(text);
+        let title = WideZString::new(title);
+        let result = unsafe { MessageBoxW(owner, text.pzwstr(), title.pzwstr(), style) };
+        match result {
+            IDOK => Ok(MessageResult::Ok),
+            IDCANCEL => Ok(MessageResult::Cancel),
+            IDYES => Ok(MessageResult::Yes),
+            IDNO => Ok(MessageResult::No),
+            IDRETRY => Ok(MessageResult::Retry),
+            _ => Err(core::Error::from_win32())?,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -90,11 +590,14 @@ struct CallbackCell<F: ?Sized>(RefCell<Callback<Box<F>>>);
 
 impl<F: ?Sized> CallbackCell<F> {
     fn set(&self, f: Option<Box<F>>) {
-        *self.0.borrow_mut() = if let Some(f) = f {
+        let new = if let Some(f) = f {
             Callback::Filled(f)
         } else {
             Callback::Empty
         };
+        // Drop the old callback outside the borrow; it may reach back here
+        let old = std::mem::replace(&mut *self.0.borrow_mut(), new);
+        drop(old);
     }
 
     fn borrow(&self) -> CallbackRef<F> {
@@ -134,24 +637,72 @@ impl<'a, F: ?Sized> Drop for CallbackRef<'a, F> {
 
 pub type Window = Rc<WindowImpl>;
 
+thread_local! {
+    // Live windows on this thread, keyed by HWND
+    static REGISTRY: RefCell<HashMap<isize, Weak<WindowImpl>>> = Default::default();
+}
+
+fn find_window(hwnd: HWND) -> Option<Window> {
+    REGISTRY
+        .with(|r| r.borrow().get(&hwnd.0).and_then(Weak::upgrade))
+        .filter(|w| w.live())
+}
+
 pub struct WindowImpl {
     hwnd: CreatedWindow,
+    thread: u32,
     callbacks: Rc<Callbacks>,
+    message_only: bool,
+    control_id: Option<u16>,
 }
 
+type PaintCallback = dyn FnMut(&PaintDC<RawHwnd>);
+
 #[derive(Default)]
 struct Callbacks {
     options: RefCell<WindowOptions>,
-    on_close: CallbackCell<dyn FnMut()>,
+    on_message: CallbackCell<dyn FnMut(u32, usize, isize) -> bool>,
+    on_paint: CallbackCell<PaintCallback>,
+    on_close: CallbackCell<dyn FnMut() -> CloseAction>,
     on_destroy: CallbackCell<dyn FnMut()>,
+    on_final: CallbackCell<dyn FnMut()>,
+    on_settings_changed: CallbackCell<dyn FnMut()>,
+    on_capture_lost: CallbackCell<dyn FnMut()>,
+    on_double_click: CallbackCell<dyn FnMut(MouseEvent)>,
+    on_resize: CallbackCell<dyn FnMut(i32, i32)>,
+    on_state_changed: CallbackCell<dyn FnMut(WindowState)>,
+    on_window_pos_changed: CallbackCell<dyn FnMut(Rect)>,
+    on_text_changed: CallbackCell<dyn FnMut()>,
+    on_value_changed: CallbackCell<dyn FnMut(i32)>,
+    on_selection_changed: CallbackCell<dyn FnMut(TreeItem)>,
+    drag_filter: CallbackCell<dyn FnMut(i32, i32) -> bool>,
+    click_through_filter: CallbackCell<dyn FnMut(i32, i32) -> bool>,
+    on_scroll: CallbackCell<dyn FnMut(ScrollBar, ScrollPos)>,
+    on_command: CallbackCell<dyn FnMut(u16)>,
+    on_files_dropped: CallbackCell<dyn FnMut(Vec<PathBuf>)>,
+    accelerators: RefCell<Option<Rc<HAccel>>>,
+    font: RefCell<Option<Rc<HFont>>>,
 
     // TODO: remove destroyed children from this list
     children: RefCell<Vec<Window>>,
+    tree_items: RefCell<HashSet<isize>>, // Live TreeView items
+    control_ids: Cell<u16>,              // Number of control ids handed out
+    default_id: Cell<Option<u16>>,       // See DM_SETDEFID
 }
 
 #[derive(Default)]
 struct WindowOptions {
     background: Option<Color>,
+    cursor: Option<Cursor>,
+    draggable: bool,
+    layered: bool,
+    state: WindowState,       // Last reported to on_state_changed
+    shown_once: bool,         // Shown by visible or show_when_ready
+    show_when_ready: bool,    // Cloaked until the first WM_PAINT
+    color_key: Option<Color>, // See Window::transparent_color
+    no_activate: bool,
+    quit_on_destroy: bool,            // See System::run
+    annotations: Option<Annotations>, // Cleared on WM_NCDESTROY
 }
 
 impl WindowImpl {
@@ -160,12 +711,18 @@ impl WindowImpl {
         window_ex_style: WINDOW_EX_STYLE,
         parent: HWND,
         control_class: Option<&str>,
+        control_id: Option<u16>,
         x: Option<i32>,
         y: Option<i32>,
         w: Option<i32>,
         h: Option<i32>,
     ) -> Result<Rc<Self>, Error> {
         let callbacks = Rc::new(Callbacks::default());
+        let visual_styles = VISUAL_STYLES.with(|v| v.borrow().clone());
+        let activation = visual_styles
+            .as_ref()
+            .map(|ctx| ctx.activate())
+            .transpose()?;
         let hwnd = CreatedWindow::new(
             callbacks.clone(),
             "",
@@ -173,12 +730,46 @@ impl WindowImpl {
             window_ex_style,
             parent,
             control_class,
+            control_id,
             x,
             y,
             w,
             h,
         )?;
-        Ok(Rc::new(Self { hwnd, callbacks }))
+        drop(activation);
+        let window = Rc::new(Self {
+            hwnd,
+            thread: GetCurrentThreadId(),
+            callbacks,
+            message_only: parent == HWND_MESSAGE,
+            control_id,
+        });
+        REGISTRY.with(|r| {
+            r.borrow_mut()
+                .insert(window.hwnd().0, Rc::downgrade(&window))
+        });
+        if !window.message_only {
+            if let Some(font) = default_font() {
+                window.send_message(WM_SETFONT, WPARAM(font.raw().0 as _), LPARAM(0))?;
+                *window.callbacks.font.borrow_mut() = Some(font);
+            }
+        }
+        Ok(window)
+    }
+
+    /// # Safety
+    /// `hwnd` must be valid and belong to the calling thread
+    unsafe fn adopt(hwnd: HWND) -> Result<Rc<Self>, Error> {
+        let callbacks = Rc::new(Callbacks::default());
+        let window = Rc::new(Self {
+            hwnd: CreatedWindow::adopt(callbacks.clone(), hwnd)?,
+            thread: GetCurrentThreadId(),
+            callbacks,
+            message_only: false,
+            control_id: None,
+        });
+        REGISTRY.with(|r| r.borrow_mut().insert(hwnd.0, Rc::downgrade(&window)));
+        Ok(window)
     }
 
     fn destroy(&self) -> Result<(), Error> {
@@ -196,6 +787,16 @@ impl WindowImpl {
         unsafe { self.hwnd.hwnd() != HWND(0) }
     }
 
+    /// SendMessageW to this window. Fails with `Error::Destroyed` without
+    /// sending once it's destroyed; a stale handle may since have been
+    /// reused by an unrelated window. Pointers in `wparam` and `lparam` only
+    /// need to outlive the call, which is synchronous.
+    fn send_message(&self, msg: u32, wparam: WPARAM, lparam: LPARAM) -> Result<LRESULT, Error> {
+        self.check_live()?;
+        // Safety: hwnd is live and belongs to this thread
+        Ok(unsafe { SendMessageW(self.hwnd(), msg, wparam, lparam) })
+    }
+
     fn check_live(&self) -> Result<(), Error> {
         if !self.live() {
             Err(Error::Destroyed)
@@ -204,81 +805,674 @@ impl WindowImpl {
         }
     }
 
-    fn set_callback<F: ?Sized>(&self, cell: &CallbackCell<F>, f: Box<F>) {
-        if self.live() {
-            cell.set(Some(f));
+    /// Body of `new_child` and `new_children`; the caller checks the thread
+    /// and liveness. Bounds go straight to CreateWindowExW, saving a
+    /// SetWindowPos.
+    fn create_child(
+        &self,
+        ty: ChildType,
+        upper_left: Option<(i32, i32)>,
+        size: Option<(i32, i32)>,
+    ) -> Result<Window, Error> {
+        let (x, y) = (upper_left.map(|p| p.0), upper_left.map(|p| p.1));
+        let (w, h) = (size.map(|s| s.0), size.map(|s| s.1));
+        let id = self.callbacks.next_control_id();
+        let is_default = matches!(ty, ChildType::DefaultButton);
+        let control = |class, style| -> Result<Window, Error> {
+            unsafe {
+                WindowImpl::new(
+                    style,
+                    Default::default(),
+                    self.hwnd(),
+                    Some(class),
+                    Some(id),
+                    x,
+                    y,
+                    w,
+                    h,
+                )
+            }
+        };
+        let child = match ty {
+            ChildType::Custom(opts) => unsafe {
+                WindowImpl::new(
+                    WS_CHILD | WS_VISIBLE | custom_options(opts),
+                    Default::default(),
+                    self.hwnd(),
+                    None,
+                    Some(id),
+                    x,
+                    y,
+                    w,
+                    h,
+                )?
+            },
+            ChildType::Button => control(
+                "BUTTON",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+            )?,
+            ChildType::DefaultButton => control(
+                "BUTTON",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | WINDOW_STYLE(BS_DEFPUSHBUTTON as u32),
+            )?,
+            ChildType::Checkbox => control(
+                "BUTTON",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | WINDOW_STYLE(BS_AUTOCHECKBOX as u32),
+            )?,
+            ChildType::TristateCheckbox => control(
+                "BUTTON",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | WINDOW_STYLE(BS_AUTO3STATE as u32),
+            )?,
+            ChildType::Groupbox => {
+                let groupbox = control(
+                    "BUTTON",
+                    WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_GROUPBOX as u32),
+                )?;
+                // Controls inside are siblings, not children. Keep the frame
+                // behind them.
+                unsafe {
+                    SetWindowPos(
+                        groupbox.hwnd(),
+                        HWND_BOTTOM,
+                        0,
+                        0,
+                        0,
+                        0,
+                        SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                    )?
+                };
+                groupbox
+            }
+            ChildType::Radio => control(
+                "BUTTON",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | WINDOW_STYLE(BS_AUTORADIOBUTTON as u32),
+            )?,
+            ChildType::Edit(opts) => control(
+                "EDIT",
+                WS_VISIBLE | WS_CHILD | WS_TABSTOP | edit_options(opts),
+            )?,
+            ChildType::UpDown => control(
+                "msctls_updown32",
+                WS_VISIBLE
+                    | WS_CHILD
+                    | WINDOW_STYLE(UDS_SETBUDDYINT | UDS_ALIGNRIGHT | UDS_ARROWKEYS),
+            )?,
+            ChildType::ListBox => control(
+                "LISTBOX",
+                WS_VISIBLE
+                    | WS_CHILD
+                    | WS_TABSTOP
+                    | WS_VSCROLL
+                    | WS_BORDER
+                    | WINDOW_STYLE(LBS_NOTIFY as u32),
+            )?,
+            ChildType::TreeView => control(
+                "SysTreeView32",
+                WS_VISIBLE
+                    | WS_CHILD
+                    | WS_TABSTOP
+                    | WS_BORDER
+                    | WINDOW_STYLE(
+                        TVS_HASBUTTONS | TVS_HASLINES | TVS_LINESATROOT | TVS_SHOWSELALWAYS,
+                    ),
+            )?,
+            // SS_LEFT is 0
+            ChildType::Label => control("STATIC", WS_VISIBLE | WS_CHILD)?,
+        };
+        self.callbacks.children.borrow_mut().push(child.clone());
+        if is_default {
+            self.send_message(DM_SETDEFID, WPARAM(id as usize), LPARAM(0))?;
         }
+        Ok(child)
     }
 
-    /// Get raw handle. May be NULL.
-    ///
-    /// # Thread Safety
-    ///
-    /// Although WindowImpl is not Send or Sync, HWND is. If you move it to another
-    /// thread, then you must account for blocking during Win32 API calls so you can avoid
-    /// deadlock. Handle lifetime (below) becomes even more challenging.
-    ///
-    /// # Safety
-    ///
-    /// Caller must not use handle after it's been destroyed. It is OK to call
-    /// [DestroyWindow].
-    pub unsafe fn hwnd(&self) -> HWND {
-        self.hwnd.hwnd()
+    /// Center over `area`, in screen coordinates, keeping the size.
+    /// `clamp` keeps the window within its monitor's work area.
+    fn center_in(&self, area: (i32, i32, i32, i32), clamp: bool) -> Result<(), Error> {
+        let (ax, ay, aw, ah) = area;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let hwnd = unsafe { RawHwnd::new(self.hwnd()) };
+        let (_, _, w, h) = get_window_rect(&hwnd)?;
+        let mut point = POINT {
+            x: ax + (aw - w) / 2,
+            y: ay + (ah - h) / 2,
+        };
+        if clamp {
+            let (wx, wy, ww, wh) = monitor_work_area(&hwnd)?;
+            point.x = point.x.min(wx + ww - w).max(wx);
+            point.y = point.y.min(wy + wh - h).max(wy);
+        }
+        unsafe {
+            let style = GetWindowLongPtrW(hwnd.raw(), GWL_STYLE) as u32;
+            if style & WS_CHILD.0 != 0 {
+                ScreenToClient(GetParent(hwnd.raw()), &mut point);
+            }
+            SetWindowPos(
+                hwnd.raw(),
+                HWND(0),
+                point.x,
+                point.y,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )?;
+        }
+        Ok(())
     }
-}
 
-impl Drop for WindowImpl {
-    fn drop(&mut self) {
-        if let Err(e) = self.destroy() {
-            eprintln!("Window::destroy failed in drop handler: {:?}", e);
+    fn check_top_level(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 {
+            return Err(Error::NotTopLevel);
         }
-        // println!("drop WindowImpl");
+        Ok(())
     }
-}
 
-impl Callbacks {
-    fn wndproc_impl(
-        &self,
-        commctrl: bool,
-        hwnd: HWND,
-        message: u32,
-        wparam: WPARAM,
-        lparam: LPARAM,
-        default: fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
-    ) -> LRESULT {
-        // Safety: hwnd is valid until we call any user-provided callbacks
-        let raw_hwnd = unsafe { RawHwnd::new(hwnd) };
+    /// Add and remove styles, then have the frame pick up the change
+    fn change_style(&self, add: WINDOW_STYLE, remove: WINDOW_STYLE) -> Result<(), Error> {
+        self.modify_window_long(GWL_STYLE, add.0, remove.0)
+    }
 
-        match message {
-            WM_PAINT => {
-                if commctrl {
-                    return default(hwnd, message, wparam, lparam);
-                }
-                // println!("WM_PAINT");
-                if let Ok(hdc) = PaintDC::new(&raw_hwnd) {
-                    if let Some(color) = self.options.borrow().background {
-                        if let Ok(brush) = HBrush::solid(color) {
-                            if let Ok((x, y, w, h)) = get_client_rect(&raw_hwnd) {
-                                fill_rect(&hdc, &brush, x, y, w, h);
-                            }
-                        }
-                    }
-                }
-                LRESULT(0)
-            }
-            WM_CLOSE => {
-                // println!("WM_CLOSE");
-                self.on_close.with(|f| f());
-                LRESULT(0)
-            }
-            WM_DESTROY => {
-                // println!("WM_DESTROY");
+    /// Add and remove extended styles, then have the frame pick up the change
+    fn change_ex_style(&self, add: WINDOW_EX_STYLE, remove: WINDOW_EX_STYLE) -> Result<(), Error> {
+        self.modify_window_long(GWL_EXSTYLE, add.0, remove.0)
+    }
+
+    fn modify_window_long(
+        &self,
+        index: WINDOW_LONG_PTR_INDEX,
+        add: u32,
+        remove: u32,
+    ) -> Result<(), Error> {
+        self.check_live()?;
+        unsafe {
+            let style = GetWindowLongPtrW(self.hwnd(), index) as u32;
+            SetWindowLongPtrW(self.hwnd(), index, ((style & !remove) | add) as isize);
+            SetWindowPos(
+                self.hwnd(),
+                HWND(0),
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE
+                    | SWP_NOSIZE
+                    | SWP_NOZORDER
+                    | SWP_NOOWNERZORDER
+                    | SWP_NOACTIVATE
+                    | SWP_FRAMECHANGED,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_displayable(&self) -> Result<(), Error> {
+        self.check_live()?;
+        if self.message_only {
+            Err(Error::MessageOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn snapshot_in(&self, order: PixelOrder) -> Result<Bitmap, Error> {
+        self.check_displayable()?;
+        unsafe {
+            let hwnd = RawHwnd::new(self.hwnd());
+            let (_, _, w, h) = get_window_rect(&hwnd)?;
+            let window_dc = WindowDC::new(&hwnd)?;
+            let bm = HBitmap::compatible(&window_dc, w, h)?;
+            let memory_dc = MemoryDc::compatible(&window_dc)?;
+            // Some controls only render correctly into PrintWindow after a
+            // real paint; this is a no-op while hidden
+            RedrawWindow(
+                hwnd.raw(),
+                None,
+                None,
+                RDW_INVALIDATE | RDW_ERASE | RDW_UPDATENOW | RDW_ALLCHILDREN,
+            );
+            select_object(&memory_dc, &bm.gdiobj(), || {
+                if PrintWindow(hwnd.raw(), memory_dc.raw(), Default::default()).0 == 0 {
+                    Err(core::Error::from_win32())?;
+                }
+                Ok(())
+            })?;
+            bitmap_from_dc(&memory_dc, &bm, h, order)
+        }
+    }
+
+    fn set_callback<F: ?Sized>(&self, cell: &CallbackCell<F>, f: Box<F>) {
+        if self.live() {
+            cell.set(Some(f));
+        }
+    }
+
+    /// Draw custom content after the background is filled. See [drawing]
+    /// for an example. Only applies to `ChildType::Custom` and main windows;
+    /// the callback must not destroy the window.
+    pub fn on_paint<F: FnMut(&PaintDC<RawHwnd>) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_paint, Box::new(callback));
+        Ok(self)
+    }
+
+    /// Get raw handle. May be NULL.
+    ///
+    /// # Thread Safety
+    ///
+    /// Although WindowImpl is not Send or Sync, HWND is. If you move it to another
+    /// thread, then you must account for blocking during Win32 API calls so you can avoid
+    /// deadlock. Handle lifetime (below) becomes even more challenging.
+    ///
+    /// # Safety
+    ///
+    /// Caller must not use handle after it's been destroyed. It is OK to call
+    /// [DestroyWindow].
+    pub unsafe fn hwnd(&self) -> HWND {
+        self.hwnd.hwnd()
+    }
+}
+
+impl Drop for WindowImpl {
+    fn drop(&mut self) {
+        // Adopted windows belong to someone else; CreatedWindow detaches
+        if self.hwnd.owned() {
+            if let Err(e) = self.destroy() {
+                eprintln!("Window::destroy failed in drop handler: {:?}", e);
+            }
+        }
+        // hwnd is already null, so prune by liveness instead of key
+        REGISTRY.with(|r| r.borrow_mut().retain(|_, w| w.strong_count() > 0));
+    }
+}
+
+impl Callbacks {
+    /// Handle a notification code reflected from the parent's WM_COMMAND
+    fn command(&self, code: u32) {
+        if code == EN_CHANGE {
+            self.on_text_changed.with(|f| f());
+        }
+    }
+
+    /// Handle a WM_NOTIFY reflected from the parent. Returns None to let the
+    /// parent's default handling run.
+    fn notify(&self, lparam: LPARAM) -> Option<LRESULT> {
+        // Safety: lparam points to an NMHDR, possibly inside a larger struct
+        //         selected by code.
+        let hdr = unsafe { &*(lparam.0 as *const NMHDR) };
+        if hdr.code == UDN_DELTAPOS {
+            let updown = unsafe { &*(lparam.0 as *const NMUPDOWN) };
+            let (mut min, mut max) = (0i32, 0i32);
+            // The pointers outlive the synchronous call
+            let sent = find_window(hdr.hwndFrom).map(|window| {
+                window.send_message(
+                    UDM_GETRANGE32,
+                    WPARAM(&mut min as *mut i32 as usize),
+                    LPARAM(&mut max as *mut i32 as isize),
+                )
+            });
+            if let Some(Ok(_)) = sent {
+                // The range may be inverted
+                let (min, max) = (min.min(max), min.max(max));
+                let value = updown.iPos.saturating_add(updown.iDelta).clamp(min, max);
+                self.on_value_changed.with(|f| f(value));
+            }
+            // Allow the change
+            return Some(LRESULT(0));
+        }
+        if hdr.code == TVN_SELCHANGEDW || hdr.code == TVN_DELETEITEMW {
+            let tree = unsafe { &*(lparam.0 as *const NMTREEVIEWW) };
+            if hdr.code == TVN_DELETEITEMW {
+                // Sent for each descendant too, so stale handles never
+                // reach the control
+                self.tree_items.borrow_mut().remove(&tree.itemOld.hItem.0);
+            } else if tree.itemNew.hItem.0 != 0 {
+                let item = TreeItem(tree.itemNew.hItem.0);
+                self.on_selection_changed.with(|f| f(item));
+            }
+            return Some(LRESULT(0));
+        }
+        None
+    }
+
+    /// Finish show_when_ready once the first WM_PAINT is done
+    fn reveal_when_painted(&self, hwnd: HWND) {
+        if !std::mem::take(&mut self.options.borrow_mut().show_when_ready) {
+            return;
+        }
+        self.options.borrow_mut().shown_once = true;
+        unsafe {
+            // Children paint after their parent; don't reveal them blank
+            RedrawWindow(hwnd, None, None, RDW_UPDATENOW | RDW_ALLCHILDREN);
+            let _ = cloak(hwnd, false);
+        }
+    }
+
+    // Break reference cycles through callbacks which capture their window
+    fn clear(&self) {
+        self.on_message.set(None);
+        self.on_paint.set(None);
+        self.on_close.set(None);
+        self.on_destroy.set(None);
+        self.on_final.set(None);
+        self.on_settings_changed.set(None);
+        self.on_capture_lost.set(None);
+        self.on_double_click.set(None);
+        self.on_resize.set(None);
+        self.on_state_changed.set(None);
+        self.on_window_pos_changed.set(None);
+        self.on_text_changed.set(None);
+        self.on_value_changed.set(None);
+        self.on_selection_changed.set(None);
+        self.drag_filter.set(None);
+        self.click_through_filter.set(None);
+        self.on_scroll.set(None);
+        self.on_command.set(None);
+        self.on_files_dropped.set(None);
+    }
+
+    fn find_child(&self, hwnd: HWND) -> Option<Window> {
+        self.children
+            .borrow()
+            .iter()
+            .find(|c| unsafe { c.hwnd() } == hwnd)
+            .cloned()
+    }
+
+    fn find_control(&self, id: u16) -> Option<Window> {
+        self.children
+            .borrow()
+            .iter()
+            .find(|c| c.control_id == Some(id) && c.live())
+            .cloned()
+    }
+
+    // Ids start above IDOK, IDCANCEL, etc., which dialog navigation sends
+    fn next_control_id(&self) -> u16 {
+        const FIRST_CONTROL_ID: u16 = 100;
+        let id = FIRST_CONTROL_ID.wrapping_add(self.control_ids.get());
+        self.control_ids.set(self.control_ids.get().wrapping_add(1));
+        id
+    }
+
+    fn wndproc_impl(
+        &self,
+        commctrl: bool,
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        default: &dyn Fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
+    ) -> LRESULT {
+        #[cfg(feature = "trace-messages")]
+        eprintln!(
+            "{:?} message {:#06x} wparam {:#x} lparam {:#x}",
+            hwnd, message, wparam.0, lparam.0
+        );
+
+        let handled = self.on_message.with(|f| f(message, wparam.0, lparam.0));
+        if handled == Some(true) && message != WM_NCDESTROY {
+            return LRESULT(0);
+        }
+        // The hook may have destroyed the window
+        if handled.is_some() && !unsafe { IsWindow(hwnd) }.as_bool() {
+            return LRESULT(0);
+        }
+
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let raw_hwnd = unsafe { RawHwnd::new(hwnd) };
+
+        match message {
+            WM_PAINT => {
+                if commctrl {
+                    return default(hwnd, message, wparam, lparam);
+                }
+                if let Ok(hdc) = PaintDC::new(&raw_hwnd) {
+                    let (background, layered) = {
+                        let options = self.options.borrow();
+                        // A color key rules out UpdateLayeredWindow
+                        (
+                            options.background,
+                            options.layered && options.color_key.is_none(),
+                        )
+                    };
+                    if layered {
+                        // Once presented through UpdateLayeredWindow, the
+                        // window ignores GDI painting, so keep using it even
+                        // for opaque colors
+                        let color = background.unwrap_or_else(|| {
+                            Color::from_colorref(unsafe { GetSysColor(COLOR_WINDOW) })
+                        });
+                        let _ = update_layered(&raw_hwnd, color);
+                    } else {
+                        fill_background(&hdc, &raw_hwnd, background);
+                        self.on_paint.with(|f| f(&hdc));
+                    }
+                }
+                self.reveal_when_painted(hwnd);
+                LRESULT(0)
+            }
+            WM_CLOSE => match self.on_close.with(|f| f()).unwrap_or_default() {
+                // DefWindowProcW destroys the window
+                CloseAction::Close => default(hwnd, message, wparam, lparam),
+                CloseAction::Cancel => LRESULT(0),
+            },
+            WM_NCHITTEST => {
+                let hit = default(hwnd, message, wparam, lparam);
+                if hit.0 != HTCLIENT as isize {
+                    return hit;
+                }
+                let mut point = POINT {
+                    x: x_lparam(lparam),
+                    y: y_lparam(lparam),
+                };
+                unsafe { ScreenToClient(hwnd, &mut point) };
+                let through = self.click_through_filter.with(|f| f(point.x, point.y));
+                if through == Some(true) {
+                    return LRESULT(HTTRANSPARENT as isize);
+                }
+                if !self.options.borrow().draggable {
+                    return hit;
+                }
+                match self.drag_filter.with(|f| f(point.x, point.y)) {
+                    Some(false) => hit,
+                    _ => LRESULT(HTCAPTION as isize),
+                }
+            }
+            WM_WINDOWPOSCHANGED => {
+                // Safety: WM_WINDOWPOSCHANGED's lparam points to a WINDOWPOS
+                let flags = unsafe { (*(lparam.0 as *const WINDOWPOS)).flags };
+                // DefWindowProcW sends WM_SIZE and WM_MOVE from here, which
+                // drives on_resize
+                let result = default(hwnd, message, wparam, lparam);
+                let moved = !flags.contains(SWP_NOMOVE) || !flags.contains(SWP_NOSIZE);
+                // WINDOWPOS holds parent-client coordinates for children
+                // Safety: on_resize may have destroyed the window
+                if moved && unsafe { IsWindow(hwnd) }.as_bool() {
+                    if let Ok(rect) = get_window_rect(&raw_hwnd) {
+                        self.on_window_pos_changed.with(|f| f(rect.into()));
+                    }
+                }
+                result
+            }
+            WM_LBUTTONDBLCLK => {
+                let event = MouseEvent {
+                    x: x_lparam(lparam),
+                    y: y_lparam(lparam),
+                    shift: wparam.0 & MK_SHIFT != 0,
+                    control: wparam.0 & MK_CONTROL != 0,
+                };
+                self.on_double_click.with(|f| f(event));
+                default(hwnd, message, wparam, lparam)
+            }
+            WM_CAPTURECHANGED => {
+                self.on_capture_lost.with(|f| f());
+                default(hwnd, message, wparam, lparam)
+            }
+            WM_SETTINGCHANGE | WM_THEMECHANGED => {
+                refresh_system_font();
+                self.on_settings_changed.with(|f| f());
+                default(hwnd, message, wparam, lparam)
+            }
+            WM_SIZE => {
+                let (w, h) = (lparam.0 & 0xffff, (lparam.0 >> 16) & 0xffff);
+                // SIZE_RESTORED also arrives for every ordinary resize
+                let state = match wparam.0 as u32 {
+                    SIZE_RESTORED => Some(WindowState::Normal),
+                    SIZE_MINIMIZED => Some(WindowState::Minimized),
+                    SIZE_MAXIMIZED => Some(WindowState::Maximized),
+                    _ => None,
+                };
+                let changed = state.filter(|&state| {
+                    std::mem::replace(&mut self.options.borrow_mut().state, state) != state
+                });
+                self.on_resize.with(|f| f(w as i32, h as i32));
+                if let Some(state) = changed {
+                    self.on_state_changed.with(|f| f(state));
+                }
+                default(hwnd, message, wparam, lparam)
+            }
+            // Only override over our own client area; children handle theirs
+            WM_SETCURSOR
+                if wparam.0 as isize == hwnd.0 && (lparam.0 & 0xffff) as u32 == HTCLIENT =>
+            {
+                match self.options.borrow().cursor {
+                    Some(cursor) => {
+                        set_cursor(cursor);
+                        LRESULT(1)
+                    }
+                    None => default(hwnd, message, wparam, lparam),
+                }
+            }
+            // lparam is null for the window's own scroll bars; scroll bar
+            // controls set it to their HWND
+            WM_HSCROLL | WM_VSCROLL if lparam.0 == 0 => {
+                let bar = if message == WM_HSCROLL {
+                    ScrollBar::Horizontal
+                } else {
+                    ScrollBar::Vertical
+                };
+                let mut info = SCROLLINFO {
+                    cbSize: size_of::<SCROLLINFO>() as u32,
+                    fMask: SIF_ALL,
+                    ..Default::default()
+                };
+                if unsafe { GetScrollInfo(hwnd, scroll_bar(bar), &mut info) }.is_ok() {
+                    if let Some(request) = scroll_request(wparam, info.nTrackPos) {
+                        let pos = ScrollPos {
+                            request,
+                            position: info.nPos,
+                            min: info.nMin,
+                            max: info.nMax,
+                            page: info.nPage as i32,
+                        };
+                        self.on_scroll.with(|f| f(bar, pos));
+                    }
+                }
+                LRESULT(0)
+            }
+            WM_COMMAND if lparam.0 != 0 => {
+                // Controls notify their parent; reflect back to the control
+                if let Some(child) = self.find_control(wparam.0 as u16) {
+                    child.callbacks.command((wparam.0 >> 16) as u32 & 0xffff);
+                }
+                default(hwnd, message, wparam, lparam)
+            }
+            // Accelerators and menus
+            WM_COMMAND => match self.on_command.with(|f| f(wparam.0 as u16)) {
+                Some(()) => LRESULT(0),
+                None => default(hwnd, message, wparam, lparam),
+            },
+            WM_NOTIFY if lparam.0 != 0 => {
+                // Safety: WM_NOTIFY's lparam points to an NMHDR
+                let from = unsafe { (*(lparam.0 as *const NMHDR)).idFrom };
+                if let Some(child) = self.find_control(from as u16) {
+                    if let Some(result) = child.callbacks.notify(lparam) {
+                        return result;
+                    }
+                }
+                default(hwnd, message, wparam, lparam)
+            }
+            // Dialog-style default button; see Button::set_default
+            DM_SETDEFID if !commctrl => {
+                let id = wparam.0 as u16;
+                let previous = self.default_id.replace(Some(id));
+                let restyle = |id: u16, style: i32| {
+                    if let Some(button) = self.find_control(id) {
+                        // lparam TRUE redraws the button
+                        let _ = button.send_message(BM_SETSTYLE, WPARAM(style as usize), LPARAM(1));
+                    }
+                };
+                if let Some(previous) = previous.filter(|&p| p != id) {
+                    restyle(previous, BS_PUSHBUTTON);
+                }
+                restyle(id, BS_DEFPUSHBUTTON);
+                LRESULT(1)
+            }
+            DM_GETDEFID if !commctrl => match self.default_id.get() {
+                Some(id) => LRESULT((DC_HASDEFID << 16 | id as u32) as isize),
+                None => LRESULT(0),
+            },
+            // PrintWindow on a hidden window renders through WM_PRINTCLIENT
+            // rather than WM_PAINT
+            WM_PRINTCLIENT if !commctrl && wparam.0 != 0 => {
+                // Safety: WM_PRINTCLIENT's wparam is an HDC valid for this call
+                let hdc = unsafe { RawHdc::new(HDC(wparam.0 as isize)) };
+                let background = self.options.borrow().background;
+                fill_background(&hdc, &raw_hwnd, background);
+                LRESULT(0)
+            }
+            // DefWindowProc doesn't remember fonts. Report the default font
+            // so measure_text on custom windows matches their controls.
+            WM_GETFONT if !commctrl => match &*self.font.borrow() {
+                Some(font) => LRESULT(unsafe { font.raw() }.0),
+                None => LRESULT(0),
+            },
+            WM_DROPFILES => {
+                // Safety: WM_DROPFILES's wparam is an HDROP which we finish
+                let hdrop = unsafe { HDrop::new(HDROP(wparam.0 as isize)) };
+                let files = hdrop.files();
+                self.on_files_dropped.with(|f| f(files));
+                LRESULT(0)
+            }
+            WM_DESTROY => {
                 self.on_destroy.with(|f| f());
+                if self.options.borrow().quit_on_destroy {
+                    unsafe { PostQuitMessage(0) };
+                }
                 default(hwnd, message, wparam, lparam)
             }
             WM_NCDESTROY => {
-                // println!("WM_NCDESTROY");
-                self.children.borrow_mut().clear();
+                // Teardown order when a parent is destroyed:
+                //   1. WM_DESTROY goes to the parent, then to each descendant
+                //   2. WM_NCDESTROY goes to the descendants, deepest first,
+                //      which nulls their hwnds
+                //   3. WM_NCDESTROY goes to the parent, which lands here
+                // By now every child's hwnd is null, so dropping the last Rc
+                // to a child doesn't call DestroyWindow again. Take the list
+                // out before dropping it: dropping a child may drop user
+                // closures which reach back into this window.
+                let children = std::mem::take(&mut *self.children.borrow_mut());
+                drop(children);
+                self.accelerators.borrow_mut().take();
+                self.font.borrow_mut().take();
+                let annotations = self.options.borrow_mut().annotations.take();
+                if let Some(annotations) = annotations {
+                    // Dynamic annotations outlive the window unless cleared
+                    let _ = unsafe {
+                        annotations.services.ClearHwndProps(
+                            hwnd,
+                            OBJID_CLIENT.0 as u32,
+                            CHILDID_SELF,
+                            &[AutomationId_Property_GUID],
+                        )
+                    };
+                }
+                self.on_final.with(|f| f());
+                self.clear();
                 default(hwnd, message, wparam, lparam)
             }
             _ => default(hwnd, message, wparam, lparam),
@@ -286,6 +1480,170 @@ impl Callbacks {
     }
 }
 
+// (x, y, w, h) in virtual screen coordinates
+fn capture_screen_rect(x: i32, y: i32, w: i32, h: i32) -> Result<Bitmap, Error> {
+    let screen_dc = ScreenDC::new()?;
+    let bm = HBitmap::compatible(&screen_dc, w, h)?;
+    let memory_dc = MemoryDc::compatible(&screen_dc)?;
+    select_object(&memory_dc, &bm.gdiobj(), || {
+        // Safety: memory_dc.raw() and screen_dc.raw() ensure both HDCs are valid.
+        //         CAPTUREBLT includes layered windows.
+        unsafe {
+            BitBlt(
+                memory_dc.raw(),
+                0,
+                0,
+                w,
+                h,
+                screen_dc.raw(),
+                x,
+                y,
+                SRCCOPY | CAPTUREBLT,
+            )?
+        };
+        Ok(())
+    })?;
+    bitmap_from_dc(&memory_dc, &bm, h, PixelOrder::Rgba)
+}
+
+// Read a 32-bit bitmap out of a memory DC. bm must not be selected into it.
+fn bitmap_from_dc(
+    memory_dc: &MemoryDc,
+    bm: &HBitmap,
+    h: i32,
+    order: PixelOrder,
+) -> Result<Bitmap, Error> {
+    // Safety: memory_dc.raw() and bm.raw() ensure HDC and HBITMAP are valid.
+    unsafe {
+        let mut bmi = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: size_of::<BITMAPINFOHEADER>() as _,
+                ..Default::default()
+            },
+            bmiColors: Default::default(),
+        };
+        if GetDIBits(
+            memory_dc.raw(),
+            bm.raw(),
+            0,
+            h as u32,
+            None,
+            &mut bmi,
+            DIB_RGB_COLORS,
+        ) == 0
+        {
+            Err(core::Error::from_win32())?;
+        }
+        bmi.bmiHeader.biHeight = -bmi.bmiHeader.biHeight.abs();
+        bmi.bmiHeader.biCompression = BI_RGB.0;
+        if bmi.bmiHeader.biBitCount != 32 || bmi.bmiHeader.biPlanes != 1 {
+            Err(Error::UnsupportedBitmapFormat)?;
+        }
+        let width = bmi.bmiHeader.biWidth as usize;
+        let height = bmi.bmiHeader.biHeight.unsigned_abs() as usize;
+        let size = bmi.bmiHeader.biSizeImage as usize;
+        if height == 0 || size % (height * 4) != 0 || size / height < width * 4 {
+            Err(Error::BitmapSize {
+                width,
+                height,
+                size,
+            })?;
+        }
+        // 32-bit rows are always DWORD-aligned, so stride == width
+        // today. Cope with padding anyway.
+        let stride = size / height / 4;
+        let mut bits = vec![0u32; size / 4];
+        if GetDIBits(
+            memory_dc.raw(),
+            bm.raw(),
+            0,
+            h as u32,
+            Some(bits.as_mut_ptr() as _),
+            &mut bmi,
+            DIB_RGB_COLORS,
+        ) == 0
+        {
+            Err(core::Error::from_win32())?;
+        }
+        if stride != width {
+            for row in 1..height {
+                bits.copy_within(row * stride..row * stride + width, row * width);
+            }
+            bits.truncate(width * height);
+        }
+        if order == PixelOrder::Rgba {
+            for pixel in &mut bits {
+                *pixel = ChannelMasks::BI_RGB.to_rgba(*pixel);
+            }
+        }
+        Ok(Bitmap {
+            width: width as u32,
+            height: height as u32,
+            data: bits,
+            order,
+        })
+    }
+}
+
+/// Hide a shown top-level window from the screen while it keeps painting
+///
+/// # Safety
+/// `hwnd` must be valid
+unsafe fn cloak(hwnd: HWND, on: bool) -> Result<(), Error> {
+    let value = BOOL::from(on);
+    DwmSetWindowAttribute(
+        hwnd,
+        DWMWA_CLOAK,
+        &value as *const BOOL as *const _,
+        size_of::<BOOL>() as u32,
+    )?;
+    Ok(())
+}
+
+/// Where a DIB keeps each channel. No alpha mask means opaque.
+#[derive(Clone, Copy)]
+struct ChannelMasks {
+    red: u32,
+    green: u32,
+    blue: u32,
+    alpha: u32,
+}
+
+impl ChannelMasks {
+    /// GDI's uncompressed layout, 0x00RRGGBB
+    const BI_RGB: Self = Self {
+        red: 0xff0000,
+        green: 0xff00,
+        blue: 0xff,
+        alpha: 0,
+    };
+
+    /// Extract into `PixelOrder::Rgba`, scaling channels to 8 bits
+    fn to_rgba(self, pixel: u32) -> u32 {
+        let channel = |mask: u32, opaque: u32| {
+            if mask == 0 {
+                return opaque;
+            }
+            let max = mask >> mask.trailing_zeros();
+            ((pixel & mask) >> mask.trailing_zeros()) * 255 / max
+        };
+        channel(self.red, 0)
+            | channel(self.green, 0) << 8
+            | channel(self.blue, 0) << 16
+            | channel(self.alpha, 255) << 24
+    }
+}
+
+fn fill_background(hdc: &impl Raw<HDC>, hwnd: &RawHwnd, background: Option<Color>) {
+    if let Some(color) = background {
+        if let Ok(brush) = HBrush::solid(color) {
+            if let Ok((x, y, w, h)) = get_client_rect(hwnd) {
+                fill_rect(hdc, &brush, x, y, w, h);
+            }
+        }
+    }
+}
+
 impl WindowProc for Rc<Callbacks> {
     unsafe fn wndproc(
         &self,
@@ -294,12 +1652,129 @@ impl WindowProc for Rc<Callbacks> {
         message: u32,
         wparam: WPARAM,
         lparam: LPARAM,
-        default: fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
+        default: &dyn Fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
     ) -> LRESULT {
         self.wndproc_impl(commctrl, hwnd, message, wparam, lparam, default)
     }
 }
 
+// Tab navigation, mnemonics, and Enter/Esc for controls in our windows
+fn translate_dialog(msg: &MSG) -> bool {
+    if msg.hwnd == HWND(0) {
+        return false;
+    }
+    let root = unsafe { GetAncestor(msg.hwnd, GA_ROOT) };
+    if find_window(root).is_none() {
+        return false;
+    }
+    // Safety: root is live for the call
+    unsafe { IsDialogMessageW(root, msg) }.as_bool()
+}
+
+// One message from the queue
+fn dispatch(msg: &MSG) {
+    if translate_accelerator(msg) || translate_dialog(msg) {
+        return;
+    }
+    // Safety: msg came from GetMessageW or PeekMessageW
+    unsafe { DispatchMessageW(msg) };
+}
+
+// Drain the queue without blocking, so tests can observe posted messages
+#[cfg(all(test, windows))]
+fn pump_messages() {
+    let mut msg = MSG::default();
+    // Safety: msg is a valid out-pointer
+    while unsafe { PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE) }.as_bool() {
+        dispatch(&msg);
+    }
+}
+
+/// Route keyboard shortcuts to the top-level window's table. Returns true
+/// if msg was consumed.
+fn translate_accelerator(msg: &MSG) -> bool {
+    if msg.hwnd == HWND(0) {
+        return false;
+    }
+    let root = unsafe { GetAncestor(msg.hwnd, GA_ROOT) };
+    let Some(window) = find_window(root) else {
+        return false;
+    };
+    // Clone so on_command may replace the table during translation
+    let Some(accelerators) = window.callbacks.accelerators.borrow().clone() else {
+        return false;
+    };
+    // Safety: root is live and accelerators is valid for the call
+    unsafe { TranslateAcceleratorW(root, accelerators.raw(), msg) != 0 }
+}
+
+// Mouse message wparam flags, from WinUser.h; the SystemServices feature
+// which has them is otherwise unused
+const MK_SHIFT: usize = 0x0004;
+const MK_CONTROL: usize = 0x0008;
+
+// Coordinates packed into lparam are signed; negative on multi-monitor setups
+// and for captured mouse input left of or above the client area
+fn x_lparam(lparam: LPARAM) -> i32 {
+    lparam.0 as i16 as i32
+}
+
+fn y_lparam(lparam: LPARAM) -> i32 {
+    (lparam.0 >> 16) as i16 as i32
+}
+
+fn set_cursor(cursor: Cursor) {
+    let id = match cursor {
+        Cursor::Arrow => IDC_ARROW,
+        Cursor::IBeam => IDC_IBEAM,
+        Cursor::Hand => IDC_HAND,
+        Cursor::Wait => IDC_WAIT,
+        Cursor::Cross => IDC_CROSS,
+        Cursor::SizeAll => IDC_SIZEALL,
+        Cursor::SizeNS => IDC_SIZENS,
+        Cursor::SizeWE => IDC_SIZEWE,
+        Cursor::No => IDC_NO,
+    };
+    // Safety: system cursors are shared and never need freeing
+    unsafe {
+        if let Ok(cursor) = LoadCursorW(None, id) {
+            SetCursor(cursor);
+        }
+    }
+}
+
+fn custom_options(opts: CustomOptions) -> WINDOW_STYLE {
+    let flag = |on, style| if on { style } else { WINDOW_STYLE(0) };
+    flag(opts.clip_siblings, WS_CLIPSIBLINGS)
+        | flag(opts.clip_children, WS_CLIPCHILDREN)
+        | flag(opts.hscroll, WS_HSCROLL)
+        | flag(opts.vscroll, WS_VSCROLL)
+        | flag(opts.border, WS_BORDER)
+}
+
+fn scroll_bar(bar: ScrollBar) -> SCROLLBAR_CONSTANTS {
+    match bar {
+        ScrollBar::Horizontal => SB_HORZ,
+        ScrollBar::Vertical => SB_VERT,
+    }
+}
+
+/// `track` comes from GetScrollInfo; wparam only holds 16 bits of it
+fn scroll_request(wparam: WPARAM, track: i32) -> Option<ScrollRequest> {
+    match SCROLLBAR_COMMAND((wparam.0 & 0xffff) as i32) {
+        SB_LINEUP => Some(ScrollRequest::LineUp),
+        SB_LINEDOWN => Some(ScrollRequest::LineDown),
+        SB_PAGEUP => Some(ScrollRequest::PageUp),
+        SB_PAGEDOWN => Some(ScrollRequest::PageDown),
+        SB_TOP => Some(ScrollRequest::Top),
+        SB_BOTTOM => Some(ScrollRequest::Bottom),
+        SB_THUMBTRACK => Some(ScrollRequest::ThumbTrack(track)),
+        SB_THUMBPOSITION => Some(ScrollRequest::ThumbPosition(track)),
+        SB_ENDSCROLL => Some(ScrollRequest::EndScroll),
+        _ => None,
+    }
+}
+
 fn edit_options(opts: EditOptions) -> WINDOW_STYLE {
     WS_CHILD
         | if opts.border { WS_BORDER } else { WS_CHILD }
@@ -320,7 +1795,9 @@ fn edit_options(opts: EditOptions) -> WINDOW_STYLE {
 
 impl crate::Window<System> for Window {
     fn system(&self) -> System {
-        System::new()
+        System {
+            thread: self.thread,
+        }
     }
 
     fn destroy(&self) -> Result<(), Error> {
@@ -329,62 +1806,44 @@ impl crate::Window<System> for Window {
     }
 
     fn new_child(&self, ty: ChildType) -> Result<Window, Error> {
+        check_thread(self.thread)?;
         self.check_live()?;
-        let control = |class, style| -> Result<Window, Error> {
-            unsafe {
-                WindowImpl::new(
-                    style,
-                    Default::default(),
-                    self.hwnd(),
-                    Some(class),
-                    None,
-                    None,
-                    None,
-                    None,
-                )
-            }
-        };
-        let child = match ty {
-            ChildType::Custom => unsafe {
-                WindowImpl::new(
-                    WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS,
-                    Default::default(),
-                    self.hwnd(),
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                )?
-            },
-            ChildType::Button => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_PUSHBUTTON as u32),
-            )?,
-            ChildType::DefaultButton => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_DEFPUSHBUTTON as u32),
-            )?,
-            ChildType::Checkbox => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_CHECKBOX as u32),
-            )?,
-            ChildType::TristateCheckbox => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_3STATE as u32),
-            )?,
-            ChildType::Groupbox => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_GROUPBOX as u32),
-            )?,
-            ChildType::Radio => control(
-                "BUTTON",
-                WS_VISIBLE | WS_CHILD | WINDOW_STYLE(BS_RADIOBUTTON as u32),
-            )?,
-            ChildType::Edit(opts) => control("EDIT", WS_VISIBLE | WS_CHILD | edit_options(opts))?,
-        };
-        self.callbacks.children.borrow_mut().push(child.clone());
-        Ok(child)
+        self.create_child(ty, None, None)
+    }
+
+    fn new_children(&self, specs: &[ChildSpec]) -> Result<Vec<Window>, Error> {
+        check_thread(self.thread)?;
+        self.check_live()?;
+        self.callbacks.children.borrow_mut().reserve(specs.len());
+        let mut children = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let child = self.create_child(spec.ty.clone(), spec.upper_left, spec.size)?;
+            if let Some(text) = &spec.text {
+                unsafe { SetWindowTextW(child.hwnd(), WideZString::new(text).pzwstr())? };
+            }
+            children.push(child);
+        }
+        Ok(children)
+    }
+
+    fn new_button(&self) -> Result<Button, Error> {
+        Ok(Button(self.new_child(ChildType::Button)?))
+    }
+
+    fn new_edit(&self, options: EditOptions) -> Result<Edit, Error> {
+        Ok(Edit(self.new_child(ChildType::Edit(options))?))
+    }
+
+    fn new_list_box(&self) -> Result<ListBox, Error> {
+        Ok(ListBox(self.new_child(ChildType::ListBox)?))
+    }
+
+    fn new_up_down(&self) -> Result<UpDown, Error> {
+        Ok(UpDown(self.new_child(ChildType::UpDown)?))
+    }
+
+    fn new_tree_view(&self) -> Result<TreeView, Error> {
+        Ok(TreeView(self.new_child(ChildType::TreeView)?))
     }
 
     fn text(self, text: &str) -> Result<Self, Error> {
@@ -395,26 +1854,31 @@ impl crate::Window<System> for Window {
         }
     }
 
+    fn title(self, title: &str) -> Result<Self, Error> {
+        self.check_top_level()?;
+        self.text(title)
+    }
+
+    fn get_title(&self) -> Result<String, Error> {
+        self.check_top_level()?;
+        self.get_text()
+    }
+
+    fn get_text(&self) -> Result<String, Error> {
+        self.check_live()?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let text = get_window_text(&unsafe { RawHwnd::new(self.hwnd()) });
+        Ok(String::from_utf16_lossy(&text))
+    }
+
     fn bounds(
         self,
         upper_left: Option<(i32, i32)>,
         size: Option<(i32, i32)>,
     ) -> Result<Self, Error> {
-        self.check_live()?;
+        self.check_displayable()?;
         unsafe {
-            let mut rect = RECT {
-                left: 0,
-                top: 0,
-                right: 0,
-                bottom: 0,
-            };
-            GetWindowRect(self.hwnd(), &mut rect)?;
-            let (mut x, mut y, mut cx, mut cy) = (
-                rect.left,
-                rect.top,
-                rect.right - rect.left,
-                rect.bottom - rect.top,
-            );
+            let (mut x, mut y, mut cx, mut cy) = get_window_bounds(&RawHwnd::new(self.hwnd()))?;
             if let Some((xx, yy)) = upper_left {
                 x = xx;
                 y = yy;
@@ -436,22 +1900,483 @@ impl crate::Window<System> for Window {
         }
     }
 
+    fn get_bounds(&self) -> Result<(i32, i32, i32, i32), Error> {
+        self.check_displayable()?;
+        unsafe { get_window_bounds(&RawHwnd::new(self.hwnd())) }
+    }
+
+    fn set_bounds(self, rect: Rect) -> Result<Self, Error> {
+        self.bounds(Some(rect.origin().into()), Some(rect.size().into()))
+    }
+
+    fn get_rect(&self) -> Result<Rect, Error> {
+        Ok(self.get_bounds()?.into())
+    }
+
+    fn center_on_screen(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let area = monitor_work_area(&unsafe { RawHwnd::new(self.hwnd()) })?;
+        self.center_in(area, false)?;
+        Ok(self)
+    }
+
+    fn center_on_parent(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        unsafe {
+            let style = GetWindowLongPtrW(self.hwnd(), GWL_STYLE) as u32;
+            if style & WS_CHILD.0 != 0 {
+                let parent = RawHwnd::new(GetParent(self.hwnd()));
+                let (_, _, w, h) = get_client_rect(&parent)?;
+                let mut origin = POINT::default();
+                ClientToScreen(parent.raw(), &mut origin);
+                self.center_in((origin.x, origin.y, w, h), false)?;
+                return Ok(self);
+            }
+            let owner = GetWindow(self.hwnd(), GW_OWNER);
+            if owner == HWND(0) {
+                return crate::Window::center_on_screen(self);
+            }
+            let area = get_window_rect(&RawHwnd::new(owner))?;
+            self.center_in(area, true)?;
+        }
+        Ok(self)
+    }
+
+    fn measure_text(&self, text: &str) -> Result<(i32, i32), Error> {
+        self.check_displayable()?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        measure_text(&unsafe { RawHwnd::new(self.hwnd()) }, text)
+    }
+
+    fn child_at(&self, x: i32, y: i32) -> Option<Window> {
+        if self.check_displayable().is_err() {
+            return None;
+        }
+        let hit = unsafe {
+            ChildWindowFromPointEx(
+                self.hwnd(),
+                POINT { x, y },
+                CWP_SKIPINVISIBLE | CWP_SKIPTRANSPARENT,
+            )
+        };
+        if hit == HWND(0) || hit == unsafe { self.hwnd() } {
+            return None;
+        }
+        self.callbacks.find_child(hit)
+    }
+
+    fn control_id(&self) -> Option<u16> {
+        self.control_id
+    }
+
+    fn parent(&self) -> Option<Window> {
+        if !self.live() {
+            return None;
+        }
+        find_window(unsafe { GetParent(self.hwnd()) })
+    }
+
+    fn children(&self) -> Vec<Window> {
+        if !self.live() {
+            return Vec::new();
+        }
+        self.callbacks
+            .children
+            .borrow()
+            .iter()
+            .filter(|c| c.live())
+            .cloned()
+            .collect()
+    }
+
     fn background(self, color: Color) -> Result<Self, Error> {
-        self.check_live()?;
+        self.check_displayable()?;
         self.callbacks.options.borrow_mut().background = Some(color);
         self.redraw()
     }
 
-    fn visible(self, visible: bool) -> Result<Self, Error> {
+    fn tool_window(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        // Windows start hidden, and the shell only decides on a taskbar
+        // button when a window is shown, so until then this is equivalent to
+        // passing the style to CreateWindowExW.
+        self.change_ex_style(WS_EX_TOOLWINDOW, WS_EX_APPWINDOW)?;
+        Ok(self)
+    }
+
+    fn chrome(self, chrome: WindowChrome) -> Result<Self, Error> {
+        self.check_top_level()?;
+        let frame = WS_OVERLAPPEDWINDOW | WS_POPUP | WS_BORDER;
+        let edges = WS_EX_WINDOWEDGE | WS_EX_CLIENTEDGE;
+        let (style, ex_style) = match chrome {
+            WindowChrome::Default => (WS_OVERLAPPEDWINDOW, edges),
+            WindowChrome::NoBorder => (WS_POPUP, Default::default()),
+            WindowChrome::ToolWindow => (
+                WS_CAPTION | WS_SYSMENU | WS_THICKFRAME,
+                edges | WS_EX_TOOLWINDOW,
+            ),
+            WindowChrome::Popup => (WS_POPUP | WS_BORDER, Default::default()),
+        };
+        self.change_style(style, frame)?;
+        // Include WS_EX_TOOLWINDOW so switching away from ToolWindow clears it
+        self.change_ex_style(ex_style, edges | WS_EX_TOOLWINDOW)?;
+        Ok(self)
+    }
+
+    fn enable_scrollbars(self, horizontal: bool, vertical: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let flag = |on, style| if on { style } else { WINDOW_STYLE(0) };
+        let add = flag(horizontal, WS_HSCROLL) | flag(vertical, WS_VSCROLL);
+        let remove = flag(!horizontal, WS_HSCROLL) | flag(!vertical, WS_VSCROLL);
+        self.change_style(add, remove)?;
+        Ok(self)
+    }
+
+    fn set_scroll_range(&self, bar: ScrollBar, min: i32, max: i32, page: i32) -> Result<(), Error> {
+        self.check_displayable()?;
+        let info = SCROLLINFO {
+            cbSize: size_of::<SCROLLINFO>() as u32,
+            fMask: SIF_RANGE | SIF_PAGE,
+            nMin: min,
+            nMax: max,
+            nPage: page.max(0) as u32,
+            ..Default::default()
+        };
+        unsafe { SetScrollInfo(self.hwnd(), scroll_bar(bar), &info, true) };
+        Ok(())
+    }
+
+    fn set_scroll_position(&self, bar: ScrollBar, position: i32) -> Result<(), Error> {
+        self.check_displayable()?;
+        let info = SCROLLINFO {
+            cbSize: size_of::<SCROLLINFO>() as u32,
+            fMask: SIF_POS,
+            nPos: position,
+            ..Default::default()
+        };
+        unsafe { SetScrollInfo(self.hwnd(), scroll_bar(bar), &info, true) };
+        Ok(())
+    }
+
+    fn rtl(self, on: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let styles = WS_EX_LAYOUTRTL | WS_EX_RTLREADING;
+        if on {
+            self.change_ex_style(styles, Default::default())?;
+        } else {
+            self.change_ex_style(Default::default(), styles)?;
+        }
+        self.redraw()
+    }
+
+    fn set_accelerators(&self, accels: &[Accelerator]) -> Result<(), Error> {
         self.check_live()?;
+        let table = if accels.is_empty() {
+            None
+        } else {
+            let accels: Vec<ACCEL> = accels
+                .iter()
+                .map(|a| {
+                    let mut flags = FVIRTKEY;
+                    if a.ctrl {
+                        flags |= FCONTROL;
+                    }
+                    if a.shift {
+                        flags |= FSHIFT;
+                    }
+                    if a.alt {
+                        flags |= FALT;
+                    }
+                    ACCEL {
+                        fVirt: flags,
+                        key: a.key.0,
+                        cmd: a.command_id,
+                    }
+                })
+                .collect();
+            Some(Rc::new(HAccel::new(&accels)?))
+        };
+        *self.callbacks.accelerators.borrow_mut() = table;
+        Ok(())
+    }
+
+    fn label_for(&self, target: &Window) -> Result<(), Error> {
+        self.check_live()?;
+        target.check_live()?;
+        unsafe {
+            // Mnemonics only search siblings
+            if GetParent(self.hwnd()) != GetParent(target.hwnd()) {
+                return Err(Error::DifferentParents);
+            }
+            // Insert after whatever precedes target, unless that's us
+            let previous = GetWindow(target.hwnd(), GW_HWNDPREV);
+            if previous != self.hwnd() {
+                SetWindowPos(
+                    self.hwnd(),
+                    if previous == HWND(0) {
+                        HWND_TOP
+                    } else {
+                        previous
+                    },
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_group(self) -> Result<Self, Error> {
+        self.change_style(WS_GROUP, Default::default())?;
+        Ok(self)
+    }
+
+    fn draggable(self, on: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        self.callbacks.options.borrow_mut().draggable = on;
+        Ok(self)
+    }
+
+    fn layered(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 {
+            return Ok(self);
+        }
+        self.change_ex_style(WS_EX_LAYERED, Default::default())?;
+        self.callbacks.options.borrow_mut().layered = true;
+        self.redraw()
+    }
+
+    fn dark_mode(self, on: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let version = os_version();
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 || version < Feature::DarkMode.min_version() {
+            return Ok(self);
+        }
+        // Builds before 20H1 used the undocumented attribute 19
+        let attribute = if version < OsVersion::new(10, 0, 18985) {
+            DWMWINDOWATTRIBUTE(19)
+        } else {
+            DWMWA_USE_IMMERSIVE_DARK_MODE
+        };
+        let value = BOOL::from(on);
+        unsafe {
+            DwmSetWindowAttribute(
+                self.hwnd(),
+                attribute,
+                &value as *const BOOL as *const _,
+                size_of::<BOOL>() as u32,
+            )?;
+        }
+        // Repaint the frame
+        self.change_style(Default::default(), Default::default())?;
+        Ok(self)
+    }
+
+    fn transparent_color(self, color: Color) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 {
+            return Ok(self);
+        }
+        self.change_ex_style(WS_EX_LAYERED, Default::default())?;
+        self.callbacks.options.borrow_mut().color_key = Some(color);
+        unsafe {
+            SetLayeredWindowAttributes(
+                self.hwnd(),
+                COLORREF(color.to_colorref()),
+                255,
+                LWA_COLORKEY,
+            )?
+        };
+        self.redraw()
+    }
+
+    fn click_through(self, on: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 {
+            return Ok(self);
+        }
+        let ex_style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_EXSTYLE) } as u32;
+        let was_layered = ex_style & WS_EX_LAYERED.0 != 0;
+        let keep_layered = {
+            let options = self.callbacks.options.borrow();
+            options.layered || options.color_key.is_some()
+        };
+        if on {
+            self.change_ex_style(WS_EX_TRANSPARENT | WS_EX_LAYERED, Default::default())?;
+            // A layered window is invisible until its attributes are set.
+            // Leave existing layering alone; it may use UpdateLayeredWindow.
+            if !was_layered {
+                unsafe { SetLayeredWindowAttributes(self.hwnd(), COLORREF(0), 255, LWA_ALPHA)? };
+            }
+        } else if keep_layered {
+            self.change_ex_style(Default::default(), WS_EX_TRANSPARENT)?;
+        } else {
+            self.change_ex_style(Default::default(), WS_EX_TRANSPARENT | WS_EX_LAYERED)?;
+        }
+        Ok(self)
+    }
+
+    fn no_activate(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        // Like tool_window, safe to set after creation: activation only
+        // happens once the window is shown or clicked.
+        self.change_ex_style(WS_EX_NOACTIVATE, Default::default())?;
+        self.callbacks.options.borrow_mut().no_activate = true;
+        Ok(self)
+    }
+
+    fn modify_style(self, add: WindowStyle, remove: WindowStyle) -> Result<Self, Error> {
+        self.check_displayable()?;
+        self.change_style(WINDOW_STYLE(add.bits()), WINDOW_STYLE(remove.bits()))?;
+        Ok(self)
+    }
+
+    fn modify_ex_style(self, add: WindowExStyle, remove: WindowExStyle) -> Result<Self, Error> {
+        self.check_displayable()?;
+        self.change_ex_style(WINDOW_EX_STYLE(add.bits()), WINDOW_EX_STYLE(remove.bits()))?;
+        Ok(self)
+    }
+
+    fn automation_id(self, id: &str) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let existing = self
+            .callbacks
+            .options
+            .borrow()
+            .annotations
+            .as_ref()
+            .map(|a| a.services.clone());
+        let services = match existing {
+            Some(services) => services,
+            None => unsafe {
+                CoCreateInstance(&CLSID_AccPropServices, None, CLSCTX_INPROC_SERVER)?
+            },
+        };
         unsafe {
-            ShowWindow(self.hwnd(), if visible { SW_SHOW } else { SW_HIDE });
+            services.SetHwndPropStr(
+                self.hwnd(),
+                OBJID_CLIENT.0 as u32,
+                CHILDID_SELF,
+                AutomationId_Property_GUID,
+                WideZString::new(id).pzwstr(),
+            )?
+        };
+        (self.callbacks.options.borrow_mut().annotations)
+            .get_or_insert_with(|| Annotations::new(services));
+        Ok(self)
+    }
+
+    fn visible(self, visible: bool) -> Result<Self, Error> {
+        self.check_displayable()?;
+        if visible {
+            let mut options = self.callbacks.options.borrow_mut();
+            options.shown_once = true;
+            if std::mem::take(&mut options.show_when_ready) {
+                let _ = unsafe { cloak(self.hwnd(), false) };
+            }
+        }
+        let show = if !visible {
+            SW_HIDE
+        } else if self.callbacks.options.borrow().no_activate {
+            SW_SHOWNA
+        } else {
+            SW_SHOW
+        };
+        unsafe {
+            ShowWindow(self.hwnd(), show);
+        }
+        Ok(self)
+    }
+
+    fn show_when_ready(self) -> Result<Self, Error> {
+        self.check_displayable()?;
+        if self.callbacks.options.borrow().shown_once {
+            return Ok(self);
+        }
+        // Hidden windows don't paint, but cloaked ones do. Cloaking is
+        // Windows 8+ and top-level only; elsewhere just show.
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & WS_CHILD.0 != 0 || unsafe { cloak(self.hwnd(), true) }.is_err() {
+            return crate::Window::visible(self, true);
+        }
+        self.callbacks.options.borrow_mut().show_when_ready = true;
+        let show = if self.callbacks.options.borrow().no_activate {
+            SW_SHOWNA
+        } else {
+            SW_SHOW
+        };
+        unsafe { ShowWindow(self.hwnd(), show) };
+        Ok(self)
+    }
+
+    fn window_state(self, state: WindowState) -> Result<Self, Error> {
+        self.check_displayable()?;
+        let show = match state {
+            WindowState::Normal => SW_RESTORE,
+            WindowState::Minimized => SW_MINIMIZE,
+            WindowState::Maximized => SW_MAXIMIZE,
+        };
+        self.callbacks.options.borrow_mut().shown_once = true;
+        unsafe { ShowWindow(self.hwnd(), show) };
+        Ok(self)
+    }
+
+    fn get_window_state(&self) -> Result<WindowState, Error> {
+        self.check_displayable()?;
+        let mut placement = WINDOWPLACEMENT {
+            length: size_of::<WINDOWPLACEMENT>() as u32,
+            ..Default::default()
+        };
+        unsafe { GetWindowPlacement(self.hwnd(), &mut placement)? };
+        Ok(match SHOW_WINDOW_CMD(placement.showCmd as i32) {
+            SW_SHOWMINIMIZED => WindowState::Minimized,
+            SW_SHOWMAXIMIZED => WindowState::Maximized,
+            _ => WindowState::Normal,
+        })
+    }
+
+    fn activate(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        if !unsafe { SetForegroundWindow(self.hwnd()) }.as_bool() {
+            return Err(Error::ForegroundDenied);
         }
+        Ok(())
+    }
+
+    fn cursor(self, cursor: Cursor) -> Result<Self, Error> {
+        self.check_displayable()?;
+        self.callbacks.options.borrow_mut().cursor = Some(cursor);
         Ok(self)
     }
 
+    fn capture_mouse(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        unsafe { SetCapture(self.hwnd()) };
+        Ok(())
+    }
+
+    fn release_mouse(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        unsafe {
+            if GetCapture() == self.hwnd() {
+                // Sends WM_CAPTURECHANGED, which calls on_capture_lost
+                ReleaseCapture()?;
+            }
+        }
+        Ok(())
+    }
+
     fn move_offscreen(self) -> Result<Self, Error> {
-        self.check_live()?;
+        self.check_displayable()?;
         unsafe {
             SetWindowPos(
                 self.hwnd(),
@@ -467,90 +2392,805 @@ impl crate::Window<System> for Window {
     }
 
     fn redraw(self) -> Result<Self, Error> {
-        self.check_live()?;
+        self.check_displayable()?;
         unsafe {
             InvalidateRect(self.hwnd(), None, true);
         }
         Ok(self)
     }
 
-    fn snapshot(&self) -> Result<Bitmap, Error> {
-        self.check_live()?;
-        unsafe {
-            let hwnd = RawHwnd::new(self.hwnd());
-            let (_, _, w, h) = get_window_rect(&hwnd)?;
-            let window_dc = WindowDC::new(&hwnd)?;
-            let bm = HBitmap::compatible(&window_dc, w, h)?;
-            let memory_dc = MemoryDc::compatible(&window_dc)?;
-            select_object(&memory_dc, &bm.gdiobj(), || {
-                if PrintWindow(hwnd.raw(), memory_dc.raw(), Default::default()).0 == 0 {
-                    Err(core::Error::from_win32())?;
-                }
-                Ok(())
-            })?;
-            let mut bmi = BITMAPINFO {
-                bmiHeader: BITMAPINFOHEADER {
-                    biSize: size_of::<BITMAPINFOHEADER>() as _,
-                    ..Default::default()
-                },
-                bmiColors: Default::default(),
-            };
-            if GetDIBits(
-                memory_dc.raw(),
-                bm.raw(),
-                0,
-                h as u32,
-                None,
-                &mut bmi,
-                DIB_RGB_COLORS,
-            ) == 0
-            {
-                Err(core::Error::from_win32())?;
-            }
-            bmi.bmiHeader.biHeight = -bmi.bmiHeader.biHeight.abs();
-            bmi.bmiHeader.biCompression = BI_RGB.0;
-            // println!("bmi: {:?}", bmi);
-            if bmi.bmiHeader.biBitCount != 32
-                || bmi.bmiHeader.biPlanes != 1
-                || bmi.bmiHeader.biSizeImage == 0
-                || bmi.bmiHeader.biSizeImage & 3 != 0
-            {
-                Err(Error::UnsupportedBitmapFormat)?;
-            }
-            let mut bits = vec![0u32; bmi.bmiHeader.biSizeImage as usize / 4];
-            if GetDIBits(
-                memory_dc.raw(),
-                bm.raw(),
-                0,
-                h as u32,
-                Some(bits.as_mut_ptr() as _),
-                &mut bmi,
-                DIB_RGB_COLORS,
-            ) == 0
-            {
+    fn invalidate_rect(&self, rect: Rect, erase: bool) -> Result<(), Error> {
+        self.check_displayable()?;
+        let rect = RECT {
+            left: rect.x,
+            top: rect.y,
+            right: rect.x + rect.w,
+            bottom: rect.y + rect.h,
+        };
+        unsafe { InvalidateRect(self.hwnd(), Some(&rect), erase).ok()? };
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        unsafe { ValidateRect(self.hwnd(), None).ok()? };
+        Ok(())
+    }
+
+    fn update(&self) -> Result<(), Error> {
+        self.check_displayable()?;
+        // Sends WM_PAINT directly, bypassing the queue
+        unsafe { UpdateWindow(self.hwnd()).ok()? };
+        Ok(())
+    }
+
+    fn set_region(&self, rects: &[Rect]) -> Result<(), Error> {
+        self.check_displayable()?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let hwnd = unsafe { RawHwnd::new(self.hwnd()) };
+        let Some((first, rest)) = rects.split_first() else {
+            if unsafe { SetWindowRgn(hwnd.raw(), HRGN(0), TRUE) } == 0 {
                 Err(core::Error::from_win32())?;
             }
-            for pixel in &mut bits {
-                *pixel = 0xff000000
-                    | ((*pixel & 0xff) << 16)
-                    | (*pixel & 0xff00)
-                    | ((*pixel & 0xff0000) >> 16);
-            }
-            Ok(Bitmap {
-                width: bmi.bmiHeader.biWidth as u32,
-                height: -bmi.bmiHeader.biHeight as u32,
-                data: bits,
-            })
+            return Ok(());
+        };
+        let mut region = HRgn::rect(first.x, first.y, first.w, first.h)?;
+        for r in rest {
+            region.union(&HRgn::rect(r.x, r.y, r.w, r.h)?)?;
         }
+        region.set_window_region(&hwnd)
+    }
+
+    fn set_round_region(
+        &self,
+        w: i32,
+        h: i32,
+        ellipse_w: i32,
+        ellipse_h: i32,
+    ) -> Result<(), Error> {
+        self.check_displayable()?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let hwnd = unsafe { RawHwnd::new(self.hwnd()) };
+        HRgn::round_rect(w, h, ellipse_w, ellipse_h)?.set_window_region(&hwnd)
+    }
+
+    fn snapshot(&self) -> Result<Bitmap, Error> {
+        self.snapshot_in(PixelOrder::Rgba)
+    }
+
+    fn snapshot_raw(&self) -> Result<Bitmap, Error> {
+        self.snapshot_in(PixelOrder::Bgra)
     }
 
-    fn on_close<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+    fn on_close<F: FnMut() -> CloseAction + 'static>(&self, callback: F) -> Result<&Self, Error> {
         self.set_callback(&self.callbacks.on_close, Box::new(callback));
         Ok(self)
     }
 
+    fn clear_callbacks(&self) -> Result<(), Error> {
+        self.callbacks.clear();
+        Ok(())
+    }
+
+    fn on_close_self<F: FnMut(&Window) -> CloseAction + 'static>(
+        &self,
+        mut callback: F,
+    ) -> Result<&Self, Error> {
+        let window = Rc::downgrade(self);
+        self.on_close(move || match window.upgrade() {
+            Some(window) => callback(&window),
+            None => CloseAction::Close,
+        })
+    }
+
     fn on_destroy<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
         self.set_callback(&self.callbacks.on_destroy, Box::new(callback));
         Ok(self)
     }
+
+    fn on_final<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_final, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_message<F: FnMut(u32, usize, isize) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_message, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_resize<F: FnMut(i32, i32) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_resize, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_window_pos_changed<F: FnMut(Rect) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_window_pos_changed, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_state_changed<F: FnMut(WindowState) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_state_changed, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_settings_changed<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_settings_changed, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_capture_lost<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_capture_lost, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_double_click<F: FnMut(MouseEvent) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_double_click, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_command<F: FnMut(u16) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_command, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_scroll<F: FnMut(ScrollBar, ScrollPos) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_scroll, Box::new(callback));
+        Ok(self)
+    }
+
+    fn drag_filter<F: FnMut(i32, i32) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.drag_filter, Box::new(callback));
+        Ok(self)
+    }
+
+    fn click_through_filter<F: FnMut(i32, i32) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.click_through_filter, Box::new(callback));
+        Ok(self)
+    }
+
+    fn on_files_dropped<F: FnMut(Vec<PathBuf>) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.check_live()?;
+        unsafe { DragAcceptFiles(self.hwnd(), true) };
+        self.set_callback(&self.callbacks.on_files_dropped, Box::new(callback));
+        Ok(self)
+    }
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+    use crate::Window as _;
+    use windows::Win32::{Graphics::Dwm::DwmFlush, UI::Controls::BST_CHECKED};
+
+    #[test]
+    fn tool_window_drops_app_window() {
+        let window = System::new()
+            .new_main()
+            .unwrap()
+            .modify_ex_style(WindowExStyle::APP_WINDOW, WindowExStyle::empty())
+            .unwrap()
+            .tool_window()
+            .unwrap();
+        let ex_style = unsafe { GetWindowLongPtrW(window.hwnd(), GWL_EXSTYLE) } as u32;
+        assert_ne!(ex_style & WS_EX_TOOLWINDOW.0, 0);
+        assert_eq!(ex_style & WS_EX_APPWINDOW.0, 0);
+
+        let sink = System::new().new_message_only().unwrap();
+        assert!(matches!(
+            sink.clone().tool_window(),
+            Err(Error::MessageOnly)
+        ));
+        assert!(matches!(sink.no_activate(), Err(Error::MessageOnly)));
+    }
+
+    // Color(255, 0, 0, 255) in PixelOrder::Rgba
+    const RED: u32 = 0xff0000ff;
+
+    fn pixel(bitmap: &Bitmap, x: u32, y: u32) -> u32 {
+        bitmap.data[(y * bitmap.width + x) as usize]
+    }
+
+    // Three buttons packed toward the leading edge of a red container
+    fn three_buttons(rtl: bool) -> Bitmap {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((500, 200)))
+            .unwrap()
+            .rtl(rtl)
+            .unwrap();
+        let container = main
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((400, 50)))
+            .unwrap()
+            .background(Color(255, 0, 0, 255))
+            .unwrap();
+        for (i, label) in ["One", "Two", "Three"].into_iter().enumerate() {
+            let x = 10 + 90 * i as i32;
+            let button = container
+                .new_child(ChildType::Button)
+                .unwrap()
+                .bounds(Some((x, 10)), Some((80, 30)))
+                .unwrap()
+                .text(label)
+                .unwrap();
+            // Mirrored parents flip the x origin; bounds must round-trip
+            assert_eq!(button.get_bounds().unwrap(), (x, 10, 80, 30));
+        }
+        container.snapshot().unwrap()
+    }
+
+    #[test]
+    fn rtl_mirrors_children() {
+        let ltr = three_buttons(false);
+        let rtl = three_buttons(true);
+        assert_eq!((ltr.width, ltr.height), (400, 50));
+        assert_eq!((rtl.width, rtl.height), (400, 50));
+        // Buttons cover x in 10..270 for LTR and 130..390 for RTL
+        assert_ne!(pixel(&ltr, 20, 25), RED);
+        assert_eq!(pixel(&ltr, 380, 25), RED);
+        assert_eq!(pixel(&rtl, 20, 25), RED);
+        assert_ne!(pixel(&rtl, 380, 25), RED);
+    }
+
+    // Counts how many times its owner is dropped
+    struct DropCounter(Rc<Cell<u32>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn destroy_tree() {
+        let root = System::new().new_main().unwrap();
+        let child = root
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((100, 100)))
+            .unwrap();
+        let grandchild = child
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((50, 50)))
+            .unwrap();
+
+        let finals = Rc::new(Cell::new(0));
+        let drops = Rc::new(Cell::new(0));
+        let tree = [root, child, grandchild];
+        for window in &tree {
+            let finals = finals.clone();
+            let counter = DropCounter(drops.clone());
+            window
+                .on_final(move || {
+                    let _ = &counter;
+                    finals.set(finals.get() + 1);
+                })
+                .unwrap();
+        }
+        let hwnds = tree.each_ref().map(|w| unsafe { w.hwnd() });
+        let weaks = tree.each_ref().map(Rc::downgrade);
+
+        tree[0].destroy().unwrap();
+        pump_messages();
+        for (window, hwnd) in tree.iter().zip(hwnds) {
+            assert!(!window.live());
+            assert_eq!(unsafe { window.hwnd() }, HWND(0));
+            assert!(find_window(hwnd).is_none());
+        }
+        assert_eq!(finals.get(), 3);
+
+        drop(tree);
+        assert!(weaks.iter().all(|w| w.strong_count() == 0));
+        assert_eq!(drops.get(), 3);
+        REGISTRY.with(|r| {
+            let r = r.borrow();
+            assert!(hwnds.iter().all(|hwnd| !r.contains_key(&hwnd.0)));
+        });
+    }
+
+    #[test]
+    fn snapshot_offscreen() {
+        // Never made visible
+        let window = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap()
+            .background(Color(255, 0, 0, 255))
+            .unwrap()
+            .move_offscreen()
+            .unwrap();
+        let bitmap = window.snapshot().unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (300, 200));
+        assert_eq!(pixel(&bitmap, 150, 100), RED);
+    }
+
+    #[test]
+    fn snapshot_odd_width() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let child = main
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((37, 20)))
+            .unwrap()
+            .background(Color(255, 0, 0, 255))
+            .unwrap();
+        let bitmap = child.snapshot().unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (37, 20));
+        assert_eq!(bitmap.data.len(), 37 * 20);
+        // The last column of each row must not bleed into the next row
+        assert!(bitmap.data.iter().all(|&p| p == RED));
+    }
+
+    #[test]
+    fn visual_styles_change_rendering() {
+        let system = System::new();
+        let main = system
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let button = |x| {
+            main.new_child(ChildType::Button)
+                .unwrap()
+                .bounds(Some((x, 0)), Some((100, 30)))
+                .unwrap()
+                .text("OK")
+                .unwrap()
+        };
+        let classic = button(0);
+        system.enable_visual_styles().unwrap();
+        let themed = button(150);
+        let (classic, themed) = (classic.snapshot().unwrap(), themed.snapshot().unwrap());
+        assert_eq!(
+            (classic.width, classic.height),
+            (themed.width, themed.height)
+        );
+        assert_ne!(classic.data, themed.data);
+    }
+
+    #[test]
+    fn dark_mode_darkens_title_bar() {
+        let system = System::new();
+        if !system.supports(Feature::DarkMode) {
+            return;
+        }
+        // DWM draws the title bar, so capture it from the screen
+        let window = system
+            .new_main()
+            .unwrap()
+            .title("Dark mode")
+            .unwrap()
+            .bounds(Some((100, 100)), Some((300, 200)))
+            .unwrap()
+            .visible(true)
+            .unwrap();
+        let title_bar = |window: &Window| {
+            pump_messages();
+            unsafe { DwmFlush() }.unwrap();
+            let hwnd = unsafe { RawHwnd::new(window.hwnd()) };
+            let (x, y, w, _) = get_window_rect(&hwnd).unwrap();
+            let mut client = POINT::default();
+            unsafe { ClientToScreen(window.hwnd(), &mut client) };
+            // The middle half, clear of the icon, title, and buttons' hover
+            let bitmap = capture_screen_rect(x + w / 4, y, w / 2, client.y - y).unwrap();
+            let total: u64 = bitmap
+                .data
+                .iter()
+                .map(|p| (p & 0xff) as u64 + (p >> 8 & 0xff) as u64 + (p >> 16 & 0xff) as u64)
+                .sum();
+            total / bitmap.data.len() as u64
+        };
+        let light = title_bar(&window);
+        let window = window.dark_mode(true).unwrap();
+        let dark = title_bar(&window);
+        assert!(dark < light, "dark {dark} should be below light {light}");
+    }
+
+    #[test]
+    fn click_through_reaches_lower_window() {
+        let system = System::new();
+        let window = || {
+            system
+                .new_main()
+                .unwrap()
+                .bounds(Some((100, 100)), Some((300, 200)))
+                .unwrap()
+                .visible(true)
+                .unwrap()
+        };
+        let lower = window();
+        let upper = window();
+        unsafe {
+            SetWindowPos(
+                upper.hwnd(),
+                HWND_TOP,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            )
+            .unwrap()
+        };
+        pump_messages();
+        let hit = || unsafe { WindowFromPoint(POINT { x: 250, y: 200 }) };
+        assert_eq!(hit(), unsafe { upper.hwnd() });
+
+        let upper = upper.click_through(true).unwrap();
+        assert_eq!(hit(), unsafe { lower.hwnd() });
+
+        let upper = upper.click_through(false).unwrap();
+        assert_eq!(hit(), unsafe { upper.hwnd() });
+    }
+
+    #[test]
+    fn checkbox_toggles_on_click() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let checkbox = main
+            .new_child(ChildType::Checkbox)
+            .unwrap()
+            .bounds(Some((10, 10)), Some((100, 20)))
+            .unwrap()
+            .text("Check")
+            .unwrap();
+        checkbox
+            .send_message(BM_CLICK, WPARAM(0), LPARAM(0))
+            .unwrap();
+        let state = checkbox
+            .send_message(BM_GETCHECK, WPARAM(0), LPARAM(0))
+            .unwrap();
+        assert_eq!(state.0, BST_CHECKED.0 as isize);
+    }
+
+    #[test]
+    fn label_for() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let edit = main
+            .new_child(ChildType::Edit(Default::default()))
+            .unwrap()
+            .bounds(Some((60, 10)), Some((100, 20)))
+            .unwrap();
+        let button = main
+            .new_child(ChildType::Button)
+            .unwrap()
+            .bounds(Some((60, 40)), Some((100, 20)))
+            .unwrap();
+        // Created last, so it starts out after both targets in z-order
+        let label = main
+            .new_child(ChildType::Label)
+            .unwrap()
+            .bounds(Some((10, 10)), Some((50, 20)))
+            .unwrap()
+            .text("&Name")
+            .unwrap();
+        label.label_for(&edit).unwrap();
+        unsafe {
+            assert_eq!(GetWindow(edit.hwnd(), GW_HWNDPREV), label.hwnd());
+            assert_eq!(GetWindow(label.hwnd(), GW_HWNDNEXT), edit.hwnd());
+        }
+
+        // Relabeling moves it again; the edit loses its label
+        label.label_for(&button).unwrap();
+        unsafe {
+            assert_eq!(GetWindow(button.hwnd(), GW_HWNDPREV), label.hwnd());
+            assert_ne!(GetWindow(edit.hwnd(), GW_HWNDPREV), label.hwnd());
+        }
+
+        let other = main
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 100)), Some((100, 50)))
+            .unwrap();
+        let nested = other.new_child(ChildType::Button).unwrap();
+        assert!(matches!(
+            label.label_for(&nested),
+            Err(Error::DifferentParents)
+        ));
+    }
+
+    #[test]
+    fn group_box_paints_behind_radios() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let container = main
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((220, 140)))
+            .unwrap()
+            .background(Color(255, 0, 0, 255))
+            .unwrap();
+        let radios = [40, 70].map(|y| {
+            container
+                .new_child(ChildType::Radio)
+                .unwrap()
+                .bounds(Some((20, y)), Some((100, 20)))
+                .unwrap()
+                .text("Radio")
+                .unwrap()
+        });
+        // Created after the radios, yet still bottommost
+        let group = container
+            .new_child(ChildType::Groupbox)
+            .unwrap()
+            .bounds(Some((10, 10)), Some((200, 120)))
+            .unwrap()
+            .text("Group")
+            .unwrap();
+        assert_eq!(unsafe { GetWindow(group.hwnd(), GW_HWNDNEXT) }, HWND(0));
+
+        let both = container.snapshot().unwrap();
+        for radio in &radios {
+            radio.clone().visible(false).unwrap();
+        }
+        let frame_only = container.snapshot().unwrap();
+        for radio in &radios {
+            radio.clone().visible(true).unwrap();
+        }
+        group.clone().visible(false).unwrap();
+        let radios_only = container.snapshot().unwrap();
+        // Each part contributes pixels the other doesn't cover
+        assert_ne!(both.data, frame_only.data);
+        assert_ne!(both.data, radios_only.data);
+        // And the frame doesn't paint over the radios
+        for y in [40, 70].into_iter().flat_map(|y| y..y + 20) {
+            for x in 20..120 {
+                assert_eq!(pixel(&both, x, y), pixel(&radios_only, x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn teardown_order() {
+        let root = System::new().new_main().unwrap();
+        let child = root
+            .new_child(ChildType::Custom(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((100, 100)))
+            .unwrap();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let logger = |entry: &'static str| {
+            let log = log.clone();
+            move || log.borrow_mut().push(entry)
+        };
+        let close = logger("close");
+        root.on_close(move || {
+            close();
+            CloseAction::Close
+        })
+        .unwrap();
+        let weak_child = Rc::downgrade(&child);
+        let root_log = log.clone();
+        root.on_destroy(move || {
+            // Children are still usable here. Panicking inside the wndproc
+            // would abort, so log instead of asserting.
+            let alive = weak_child.upgrade().is_some_and(|c| c.live());
+            root_log.borrow_mut().push(if alive {
+                "destroy root"
+            } else {
+                "destroy root after child"
+            });
+        })
+        .unwrap();
+        root.on_final(logger("final root")).unwrap();
+        child.on_destroy(logger("destroy child")).unwrap();
+        child.on_final(logger("final child")).unwrap();
+
+        root.send_message(WM_CLOSE, WPARAM(0), LPARAM(0)).unwrap();
+        pump_messages();
+        assert_eq!(
+            *log.borrow(),
+            [
+                "close",
+                "destroy root",
+                "destroy child",
+                "final child",
+                "final root"
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_after_destroy() {
+        let window = System::new().new_main().unwrap();
+        window.destroy().unwrap();
+        pump_messages();
+        assert!(!window.live());
+        // Nothing left to destroy
+        WindowImpl::destroy(&window).unwrap();
+
+        // DestroyWindow on a stale handle would set ERROR_INVALID_WINDOW_HANDLE
+        unsafe { SetLastError(ERROR_SUCCESS) };
+        drop(window);
+        assert!(unsafe { GetLastError() }.is_ok());
+    }
+
+    #[test]
+    fn settings_change_refreshes_font() {
+        let main = System::new().new_main().unwrap();
+        let button = main
+            .new_child(ChildType::Button)
+            .unwrap()
+            .bounds(Some((0, 0)), Some((100, 30)))
+            .unwrap();
+        let changed = Rc::new(Cell::new(false));
+        main.on_settings_changed({
+            let changed = changed.clone();
+            move || changed.set(true)
+        })
+        .unwrap();
+
+        // Pretend the cached font came from a different message font
+        let old = SYSTEM_FONT.with(|f| {
+            let mut f = f.borrow_mut();
+            let (lf, font) = f.as_mut().unwrap();
+            lf.lfHeight += 1;
+            font.clone()
+        });
+        assert!(button
+            .callbacks
+            .font
+            .borrow()
+            .as_ref()
+            .is_some_and(|f| Rc::ptr_eq(f, &old)));
+
+        main.send_message(WM_SETTINGCHANGE, WPARAM(0), LPARAM(0))
+            .unwrap();
+        assert!(changed.get());
+        let new = SYSTEM_FONT.with(|f| f.borrow().as_ref().unwrap().1.clone());
+        assert!(!Rc::ptr_eq(&new, &old));
+        assert!(button
+            .callbacks
+            .font
+            .borrow()
+            .as_ref()
+            .is_some_and(|f| Rc::ptr_eq(f, &new)));
+    }
+
+    #[test]
+    fn default_font_is_message_font() {
+        let main = System::new().new_main().unwrap();
+        let button = main.new_child(ChildType::Button).unwrap();
+        let font = button
+            .send_message(WM_GETFONT, WPARAM(0), LPARAM(0))
+            .unwrap();
+        let shared = SYSTEM_FONT.with(|f| f.borrow().as_ref().unwrap().1.clone());
+        assert_eq!(HFONT(font.0), unsafe { shared.raw() });
+
+        let mut lf = LOGFONTW::default();
+        let size = unsafe {
+            GetObjectW(
+                HFONT(font.0),
+                size_of::<LOGFONTW>() as i32,
+                Some(&mut lf as *mut _ as *mut _),
+            )
+        };
+        assert_eq!(size as usize, size_of::<LOGFONTW>());
+        let message = HFont::message_logfont().unwrap();
+        assert_eq!(lf.lfFaceName, message.lfFaceName);
+    }
+
+    #[test]
+    fn redraw_update_snapshot() {
+        let window = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap()
+            .move_offscreen()
+            .unwrap()
+            .visible(true)
+            .unwrap();
+        for (color, expected) in [
+            (Color(255, 0, 0, 255), RED),
+            (Color(0, 255, 0, 255), 0xff00ff00),
+        ] {
+            let window = window.clone().background(color).unwrap().redraw().unwrap();
+            window.update().unwrap();
+            let bitmap = window.snapshot().unwrap();
+            assert_eq!(pixel(&bitmap, 150, 100), expected);
+        }
+    }
+
+    #[test]
+    fn double_click_fires_once() {
+        const MK_LBUTTON: usize = 0x0001;
+
+        let window = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((300, 200)))
+            .unwrap();
+        let style = unsafe { GetClassLongPtrW(window.hwnd(), GCL_STYLE) } as u32;
+        assert_ne!(style & CS_DBLCLKS.0, 0);
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        window
+            .on_double_click({
+                let events = events.clone();
+                move |event| events.borrow_mut().push(event)
+            })
+            .unwrap();
+
+        // What Windows sends for a double-click on a CS_DBLCLKS window
+        let point = LPARAM((20 << 16) | 10);
+        for (message, wparam) in [
+            (WM_LBUTTONDOWN, MK_LBUTTON),
+            (WM_LBUTTONUP, 0),
+            (WM_LBUTTONDBLCLK, MK_LBUTTON | MK_SHIFT),
+            (WM_LBUTTONUP, 0),
+        ] {
+            window.send_message(message, WPARAM(wparam), point).unwrap();
+        }
+        assert_eq!(
+            *events.borrow(),
+            [MouseEvent {
+                x: 10,
+                y: 20,
+                shift: true,
+                control: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn com_outlives_event_loop() {
+        let system = System::with_com().unwrap();
+        system.run_with_timeout(Duration::from_millis(10)).unwrap();
+        // CoCreateInstance fails with CO_E_NOTINITIALIZED once COM is gone
+        system.new_main().unwrap().automation_id("main").unwrap();
+    }
+
+    #[test]
+    fn annotations_keep_com() {
+        unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.unwrap();
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .automation_id("main")
+            .unwrap();
+        unsafe { CoUninitialize() };
+        // The window's services still hold an initialization for WM_NCDESTROY
+        let services: Result<IAccPropServices, _> =
+            unsafe { CoCreateInstance(&CLSID_AccPropServices, None, CLSCTX_INPROC_SERVER) };
+        assert!(services.is_ok());
+        drop(services);
+        main.destroy().unwrap();
+    }
 }
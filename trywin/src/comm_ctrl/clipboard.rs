@@ -0,0 +1,275 @@
+// Clipboard
+
+use std::mem::size_of;
+use windows::{
+    core,
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::{BITMAPINFOHEADER, BITMAPV5HEADER, BI_BITFIELDS, BI_RGB},
+        System::{
+            DataExchange::{
+                CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData,
+                IsClipboardFormatAvailable, OpenClipboard, SetClipboardData,
+            },
+            Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+        },
+    },
+};
+
+use super::{object_wrappers::*, ChannelMasks, Window};
+use crate::{swap_red_blue, Bitmap, PixelOrder};
+
+// From WinUser.h; the Ole feature which defines them is large
+pub const CF_DIB: u32 = 8;
+pub const CF_DIBV5: u32 = 17;
+
+// From WinGDI.h
+const BITMAPINFOHEADER_SIZE: usize = 40;
+const BITMAPV3HEADER_SIZE: usize = 56; // Adds the alpha mask
+const LCS_SRGB: u32 = 0x73524742;
+const LCS_GM_IMAGES: u32 = 4;
+
+// Open for the lifetime of the guard
+struct Open;
+
+impl Open {
+    fn new(owner: HWND) -> Result<Self, Error> {
+        unsafe { OpenClipboard(owner)? };
+        Ok(Open)
+    }
+}
+
+impl Drop for Open {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseClipboard();
+        }
+    }
+}
+
+/// Formats on the clipboard, in the order the source offered them. Includes
+/// formats Windows synthesizes, e.g. `CF_DIB` from `CF_DIBV5`.
+pub fn formats() -> Result<Vec<u32>, Error> {
+    let _open = Open::new(HWND(0))?;
+    let mut formats = Vec::new();
+    let mut format = 0;
+    loop {
+        // 0 means either the end or an error; only errors set the last error
+        unsafe { SetLastError(NO_ERROR) };
+        format = unsafe { EnumClipboardFormats(format) };
+        if format == 0 {
+            break;
+        }
+        formats.push(format);
+    }
+    let err = core::Error::from_win32();
+    if err.code().is_err() {
+        Err(err)?;
+    }
+    Ok(formats)
+}
+
+/// Replace the clipboard contents with `bitmap` as `CF_DIBV5`, with straight
+/// alpha. Bitmaps whose alpha is 0 everywhere, e.g. `snapshot_raw`, are
+/// marked opaque rather than copied as invisible. `owner` becomes the
+/// clipboard owner; Windows requires one.
+pub fn set_image(owner: &Window, bitmap: &Bitmap) -> Result<(), Error> {
+    owner.check_live()?;
+    let (width, height) = (bitmap.width as usize, bitmap.height as usize);
+    if bitmap.data.len() != width * height {
+        return Err(Error::BitmapSize {
+            width,
+            height,
+            size: bitmap.data.len() * 4,
+        });
+    }
+    let has_alpha = bitmap.data.iter().any(|p| p >> 24 != 0);
+    let header = BITMAPV5HEADER {
+        bV5Size: size_of::<BITMAPV5HEADER>() as u32,
+        bV5Width: width as i32,
+        // Bottom-up; some readers mishandle top-down clipboard DIBs
+        bV5Height: height as i32,
+        bV5Planes: 1,
+        bV5BitCount: 32,
+        bV5Compression: BI_BITFIELDS,
+        bV5SizeImage: (width * height * 4) as u32,
+        bV5RedMask: 0x00ff0000,
+        bV5GreenMask: 0x0000ff00,
+        bV5BlueMask: 0x000000ff,
+        bV5AlphaMask: if has_alpha { 0xff000000 } else { 0 },
+        bV5CSType: LCS_SRGB,
+        bV5Intent: LCS_GM_IMAGES,
+        ..Default::default()
+    };
+    let mut dib: Vec<u32> = Vec::with_capacity(width * height);
+    for row in bitmap.data.chunks(width.max(1)).rev() {
+        dib.extend(row.iter().map(|&p| match bitmap.order {
+            PixelOrder::Rgba => swap_red_blue(p),
+            PixelOrder::Bgra => p,
+        }));
+    }
+
+    let _open = Open::new(unsafe { owner.hwnd() })?;
+    unsafe {
+        EmptyClipboard()?;
+        let size = size_of::<BITMAPV5HEADER>() + dib.len() * 4;
+        let memory = GlobalAlloc(GMEM_MOVEABLE, size)?;
+        let p = GlobalLock(memory) as *mut u8;
+        if p.is_null() {
+            let err = core::Error::from_win32();
+            let _ = GlobalFree(memory);
+            Err(err)?;
+        }
+        // Safety: memory holds size bytes; GlobalAlloc aligns it for u32
+        std::ptr::copy_nonoverlapping(
+            &header as *const BITMAPV5HEADER as *const u8,
+            p,
+            size_of::<BITMAPV5HEADER>(),
+        );
+        std::ptr::copy_nonoverlapping(
+            dib.as_ptr() as *const u8,
+            p.add(size_of::<BITMAPV5HEADER>()),
+            dib.len() * 4,
+        );
+        let _ = GlobalUnlock(memory);
+        // The clipboard owns memory on success
+        if let Err(err) = SetClipboardData(CF_DIBV5, HANDLE(memory.0 as isize)) {
+            let _ = GlobalFree(memory);
+            Err(err)?;
+        }
+    }
+    Ok(())
+}
+
+/// The clipboard's image in `PixelOrder::Rgba`, or None if it has none.
+/// Reads 24- and 32-bit DIBs; alpha comes from the source's alpha mask,
+/// and is opaque without one. Fails on other depths and compressed DIBs.
+pub fn get_image() -> Result<Option<Bitmap>, Error> {
+    let _open = Open::new(HWND(0))?;
+    // Windows synthesizes each from the other; V5 keeps the alpha mask
+    let format = if unsafe { IsClipboardFormatAvailable(CF_DIBV5) }.is_ok() {
+        CF_DIBV5
+    } else if unsafe { IsClipboardFormatAvailable(CF_DIB) }.is_ok() {
+        CF_DIB
+    } else {
+        return Ok(None);
+    };
+    unsafe {
+        let memory = HGLOBAL(GetClipboardData(format)?.0 as *mut _);
+        let p = GlobalLock(memory) as *const u8;
+        if p.is_null() {
+            Err(core::Error::from_win32())?;
+        }
+        // Safety: the clipboard owns memory, which holds GlobalSize bytes,
+        //         until CloseClipboard
+        let bytes = std::slice::from_raw_parts(p, GlobalSize(memory));
+        let result = parse_dib(bytes);
+        let _ = GlobalUnlock(memory);
+        result.map(Some)
+    }
+}
+
+fn parse_dib(bytes: &[u8]) -> Result<Bitmap, Error> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    if bytes.len() < BITMAPINFOHEADER_SIZE {
+        return Err(Error::UnsupportedBitmapFormat);
+    }
+    // Safety: bytes holds at least a BITMAPINFOHEADER, which every DIB
+    //         header starts with
+    let info = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const BITMAPINFOHEADER) };
+    let header_size = info.biSize as usize;
+    let width = info.biWidth.unsigned_abs() as usize;
+    let height = info.biHeight.unsigned_abs() as usize;
+    let bottom_up = info.biHeight > 0;
+    let bits = info.biBitCount as usize;
+
+    let mut offset = header_size;
+    let masks = match (bits, info.biCompression) {
+        (24, c) if c == BI_RGB.0 => ChannelMasks::BI_RGB,
+        (32, c) if c == BI_RGB.0 => ChannelMasks::BI_RGB,
+        (32, c) if c == BI_BITFIELDS.0 => {
+            // Later headers hold the masks; BITMAPINFOHEADER is followed
+            // by them
+            if header_size == BITMAPINFOHEADER_SIZE {
+                offset += 12;
+            }
+            let mask = |i: usize| {
+                u32_at(BITMAPINFOHEADER_SIZE + i * 4).ok_or(Error::UnsupportedBitmapFormat)
+            };
+            ChannelMasks {
+                red: mask(0)?,
+                green: mask(1)?,
+                blue: mask(2)?,
+                alpha: if header_size >= BITMAPV3HEADER_SIZE {
+                    mask(3)?
+                } else {
+                    0
+                },
+            }
+        }
+        _ => return Err(Error::UnsupportedBitmapFormat),
+    };
+    let offset = (info.biClrUsed as usize)
+        .checked_mul(4)
+        .and_then(|colors| offset.checked_add(colors))
+        .ok_or(Error::UnsupportedBitmapFormat)?;
+
+    // Headers come from other processes, so sizes may not fit in usize
+    let too_small = || Error::BitmapSize {
+        width,
+        height,
+        size: bytes.len().saturating_sub(offset),
+    };
+    // Rows are DWORD-aligned
+    let stride = width.checked_mul(bits).ok_or_else(too_small)?.div_ceil(32) * 4;
+    let pixels = stride
+        .checked_mul(height)
+        .and_then(|size| bytes.get(offset..offset.checked_add(size)?))
+        .ok_or_else(too_small)?;
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = if bottom_up { height - 1 - y } else { y };
+        let row = &pixels[row * stride..][..width * bits / 8];
+        data.extend(row.chunks_exact(bits / 8).map(|p| {
+            let pixel = p
+                .iter()
+                .rev()
+                .fold(0u32, |acc, &byte| acc << 8 | byte as u32);
+            masks.to_rgba(pixel)
+        }));
+    }
+    Ok(Bitmap {
+        width: width as u32,
+        height: height as u32,
+        data,
+        order: PixelOrder::Rgba,
+    })
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dib_rejects_huge_sizes() {
+        let info = BITMAPINFOHEADER {
+            biSize: BITMAPINFOHEADER_SIZE as u32,
+            biWidth: i32::MAX,
+            biHeight: i32::MAX,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        };
+        // Safety: BITMAPINFOHEADER is plain data
+        let bytes = unsafe {
+            std::slice::from_raw_parts(&info as *const _ as *const u8, BITMAPINFOHEADER_SIZE)
+        };
+        // The pixel array alone would exceed usize
+        assert!(matches!(parse_dib(bytes), Err(Error::BitmapSize { .. })));
+    }
+}
@@ -0,0 +1,785 @@
+use closure_attr::{Downgrade, Upgrade};
+use std::{ops::Deref, path::PathBuf, rc::Weak};
+use windows::{
+    core::PWSTR,
+    Win32::{
+        Foundation::*,
+        UI::{
+            Controls::{
+                EM_GETFIRSTVISIBLELINE, EM_GETLINECOUNT, EM_GETSEL, EM_LINESCROLL, EM_REPLACESEL,
+                EM_SETLIMITTEXT, EM_SETSEL, HTREEITEM, TVE_COLLAPSE, TVE_EXPAND, TVGN_CARET,
+                TVIF_TEXT, TVINSERTSTRUCTW, TVINSERTSTRUCTW_0, TVITEMW, TVI_LAST, TVI_ROOT,
+                TVM_DELETEITEM, TVM_EXPAND, TVM_INSERTITEMW, TVM_SELECTITEM, UDM_SETBUDDY,
+                UDM_SETPOS32, UDM_SETRANGE32,
+            },
+            WindowsAndMessaging::{
+                GetParent, GetWindowLongPtrW, DM_SETDEFID, ES_MULTILINE, GWL_STYLE, LB_ADDSTRING,
+                LB_ERR, LB_ERRSPACE,
+            },
+        },
+    },
+};
+
+use super::{find_window, object_wrappers::*, System, Window, WindowImpl};
+use crate::{
+    Accelerator, Bitmap, ChildSpec, ChildType, CloseAction, Color, Cursor, EditOptions, MouseEvent,
+    Rect, ScrollBar, ScrollPos, TreeItem, Window as WindowTrait, WindowChrome, WindowExStyle,
+    WindowState, WindowStyle,
+};
+
+// Typed handle around an untyped child. Shared attributes are reachable
+// through Deref and the full Window delegation below; control-specific
+// methods are implemented on the generated type.
+macro_rules! control {
+    ($name:ident, $weak:ident) => {
+        #[derive(Clone)]
+        pub struct $name(pub(super) Window);
+
+        #[derive(Clone)]
+        pub struct $weak(Weak<WindowImpl>);
+
+        impl Deref for $name {
+            type Target = Window;
+            fn deref(&self) -> &Window {
+                &self.0
+            }
+        }
+
+        impl Downgrade for $name {
+            type Target = $weak;
+            fn downgrade(this: &Self) -> $weak {
+                $weak(std::rc::Rc::downgrade(&this.0))
+            }
+        }
+
+        impl Upgrade for $weak {
+            type Target = $name;
+            fn upgrade(&self) -> Option<$name> {
+                self.0.upgrade().map($name)
+            }
+        }
+
+        impl WindowTrait<System> for $name {
+            fn system(&self) -> System {
+                WindowTrait::system(&self.0)
+            }
+
+            fn destroy(&self) -> Result<(), Error> {
+                WindowTrait::destroy(&self.0)
+            }
+
+            fn new_child(&self, ty: ChildType) -> Result<Window, Error> {
+                WindowTrait::new_child(&self.0, ty)
+            }
+
+            fn new_children(&self, specs: &[ChildSpec]) -> Result<Vec<Window>, Error> {
+                WindowTrait::new_children(&self.0, specs)
+            }
+
+            fn new_button(&self) -> Result<Button, Error> {
+                WindowTrait::new_button(&self.0)
+            }
+
+            fn new_edit(&self, options: EditOptions) -> Result<Edit, Error> {
+                WindowTrait::new_edit(&self.0, options)
+            }
+
+            fn new_list_box(&self) -> Result<ListBox, Error> {
+                WindowTrait::new_list_box(&self.0)
+            }
+
+            fn new_up_down(&self) -> Result<UpDown, Error> {
+                WindowTrait::new_up_down(&self.0)
+            }
+
+            fn new_tree_view(&self) -> Result<TreeView, Error> {
+                WindowTrait::new_tree_view(&self.0)
+            }
+
+            fn text(self, text: &str) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::text(self.0, text)?))
+            }
+
+            fn title(self, title: &str) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::title(self.0, title)?))
+            }
+
+            fn get_title(&self) -> Result<String, Error> {
+                WindowTrait::get_title(&self.0)
+            }
+
+            fn get_text(&self) -> Result<String, Error> {
+                WindowTrait::get_text(&self.0)
+            }
+
+            fn bounds(
+                self,
+                upper_left: Option<(i32, i32)>,
+                size: Option<(i32, i32)>,
+            ) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::bounds(self.0, upper_left, size)?))
+            }
+
+            fn get_bounds(&self) -> Result<(i32, i32, i32, i32), Error> {
+                WindowTrait::get_bounds(&self.0)
+            }
+
+            fn set_bounds(self, rect: Rect) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::set_bounds(self.0, rect)?))
+            }
+
+            fn center_on_screen(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::center_on_screen(self.0)?))
+            }
+
+            fn center_on_parent(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::center_on_parent(self.0)?))
+            }
+
+            fn get_rect(&self) -> Result<Rect, Error> {
+                WindowTrait::get_rect(&self.0)
+            }
+
+            fn measure_text(&self, text: &str) -> Result<(i32, i32), Error> {
+                WindowTrait::measure_text(&self.0, text)
+            }
+
+            fn child_at(&self, x: i32, y: i32) -> Option<Window> {
+                WindowTrait::child_at(&self.0, x, y)
+            }
+
+            fn control_id(&self) -> Option<u16> {
+                WindowTrait::control_id(&self.0)
+            }
+
+            fn parent(&self) -> Option<Window> {
+                WindowTrait::parent(&self.0)
+            }
+
+            fn children(&self) -> Vec<Window> {
+                WindowTrait::children(&self.0)
+            }
+
+            fn background(self, color: Color) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::background(self.0, color)?))
+            }
+
+            fn tool_window(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::tool_window(self.0)?))
+            }
+
+            fn chrome(self, chrome: WindowChrome) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::chrome(self.0, chrome)?))
+            }
+
+            fn enable_scrollbars(self, horizontal: bool, vertical: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::enable_scrollbars(
+                    self.0, horizontal, vertical,
+                )?))
+            }
+
+            fn set_scroll_range(
+                &self,
+                bar: ScrollBar,
+                min: i32,
+                max: i32,
+                page: i32,
+            ) -> Result<(), Error> {
+                WindowTrait::set_scroll_range(&self.0, bar, min, max, page)
+            }
+
+            fn set_scroll_position(&self, bar: ScrollBar, position: i32) -> Result<(), Error> {
+                WindowTrait::set_scroll_position(&self.0, bar, position)
+            }
+
+            fn rtl(self, on: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::rtl(self.0, on)?))
+            }
+
+            fn set_accelerators(&self, accels: &[Accelerator]) -> Result<(), Error> {
+                WindowTrait::set_accelerators(&self.0, accels)
+            }
+
+            fn label_for(&self, target: &Window) -> Result<(), Error> {
+                WindowTrait::label_for(&self.0, target)
+            }
+
+            fn begin_group(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::begin_group(self.0)?))
+            }
+
+            fn draggable(self, on: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::draggable(self.0, on)?))
+            }
+
+            fn layered(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::layered(self.0)?))
+            }
+
+            fn transparent_color(self, color: Color) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::transparent_color(self.0, color)?))
+            }
+
+            fn click_through(self, on: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::click_through(self.0, on)?))
+            }
+
+            fn dark_mode(self, on: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::dark_mode(self.0, on)?))
+            }
+
+            fn no_activate(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::no_activate(self.0)?))
+            }
+
+            fn modify_style(self, add: WindowStyle, remove: WindowStyle) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::modify_style(self.0, add, remove)?))
+            }
+
+            fn modify_ex_style(
+                self,
+                add: WindowExStyle,
+                remove: WindowExStyle,
+            ) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::modify_ex_style(self.0, add, remove)?))
+            }
+
+            fn automation_id(self, id: &str) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::automation_id(self.0, id)?))
+            }
+
+            fn move_offscreen(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::move_offscreen(self.0)?))
+            }
+
+            fn visible(self, visible: bool) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::visible(self.0, visible)?))
+            }
+
+            fn show_when_ready(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::show_when_ready(self.0)?))
+            }
+
+            fn window_state(self, state: WindowState) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::window_state(self.0, state)?))
+            }
+
+            fn get_window_state(&self) -> Result<WindowState, Error> {
+                WindowTrait::get_window_state(&self.0)
+            }
+
+            fn activate(&self) -> Result<(), Error> {
+                WindowTrait::activate(&self.0)
+            }
+
+            fn cursor(self, cursor: Cursor) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::cursor(self.0, cursor)?))
+            }
+
+            fn capture_mouse(&self) -> Result<(), Error> {
+                WindowTrait::capture_mouse(&self.0)
+            }
+
+            fn release_mouse(&self) -> Result<(), Error> {
+                WindowTrait::release_mouse(&self.0)
+            }
+
+            fn redraw(self) -> Result<Self, Error> {
+                Ok(Self(WindowTrait::redraw(self.0)?))
+            }
+
+            fn invalidate_rect(&self, rect: Rect, erase: bool) -> Result<(), Error> {
+                WindowTrait::invalidate_rect(&self.0, rect, erase)
+            }
+
+            fn validate(&self) -> Result<(), Error> {
+                WindowTrait::validate(&self.0)
+            }
+
+            fn update(&self) -> Result<(), Error> {
+                WindowTrait::update(&self.0)
+            }
+
+            fn set_region(&self, rects: &[Rect]) -> Result<(), Error> {
+                WindowTrait::set_region(&self.0, rects)
+            }
+
+            fn set_round_region(
+                &self,
+                w: i32,
+                h: i32,
+                ellipse_w: i32,
+                ellipse_h: i32,
+            ) -> Result<(), Error> {
+                WindowTrait::set_round_region(&self.0, w, h, ellipse_w, ellipse_h)
+            }
+
+            fn snapshot_raw(&self) -> Result<Bitmap, Error> {
+                WindowTrait::snapshot_raw(&self.0)
+            }
+
+            fn snapshot(&self) -> Result<Bitmap, Error> {
+                WindowTrait::snapshot(&self.0)
+            }
+
+            fn on_close<F: FnMut() -> CloseAction + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_close(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn clear_callbacks(&self) -> Result<(), Error> {
+                WindowTrait::clear_callbacks(&self.0)
+            }
+
+            fn on_close_self<F: FnMut(&Window) -> CloseAction + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_close_self(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_destroy<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+                WindowTrait::on_destroy(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_final<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+                WindowTrait::on_final(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_message<F: FnMut(u32, usize, isize) -> bool + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_message(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_resize<F: FnMut(i32, i32) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+                WindowTrait::on_resize(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_window_pos_changed<F: FnMut(Rect) + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_window_pos_changed(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_state_changed<F: FnMut(WindowState) + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_state_changed(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_settings_changed<F: FnMut() + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_settings_changed(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_capture_lost<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+                WindowTrait::on_capture_lost(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_double_click<F: FnMut(MouseEvent) + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_double_click(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_command<F: FnMut(u16) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+                WindowTrait::on_command(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_scroll<F: FnMut(ScrollBar, ScrollPos) + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_scroll(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn drag_filter<F: FnMut(i32, i32) -> bool + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::drag_filter(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn click_through_filter<F: FnMut(i32, i32) -> bool + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::click_through_filter(&self.0, callback)?;
+                Ok(self)
+            }
+
+            fn on_files_dropped<F: FnMut(Vec<PathBuf>) + 'static>(
+                &self,
+                callback: F,
+            ) -> Result<&Self, Error> {
+                WindowTrait::on_files_dropped(&self.0, callback)?;
+                Ok(self)
+            }
+        }
+    };
+}
+
+control!(Button, WeakButton);
+control!(Edit, WeakEdit);
+control!(ListBox, WeakListBox);
+control!(UpDown, WeakUpDown);
+control!(TreeView, WeakTreeView);
+
+impl crate::Button<System> for Button {
+    fn set_default(&self) -> Result<(), Error> {
+        self.check_live()?;
+        let parent = find_window(unsafe { GetParent(self.hwnd()) });
+        if let (Some(id), Some(parent)) = (self.control_id, parent) {
+            parent.send_message(DM_SETDEFID, WPARAM(id as usize), LPARAM(0))?;
+        }
+        Ok(())
+    }
+}
+
+impl Edit {
+    fn check_multiline(&self) -> Result<(), Error> {
+        self.check_live()?;
+        let style = unsafe { GetWindowLongPtrW(self.hwnd(), GWL_STYLE) } as u32;
+        if style & ES_MULTILINE as u32 == 0 {
+            return Err(Error::NotMultiline);
+        }
+        Ok(())
+    }
+}
+
+impl crate::Edit<System> for Edit {
+    fn on_text_changed<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_text_changed, Box::new(callback));
+        Ok(self)
+    }
+
+    fn set_selection(&self, start: u32, end: u32) -> Result<(), Error> {
+        // EM_SETSEL treats -1 as "to end"
+        let end = if end == u32::MAX { -1 } else { end as isize };
+        self.send_message(EM_SETSEL, WPARAM(start as usize), LPARAM(end))?;
+        Ok(())
+    }
+
+    fn selected_text(&self) -> Result<String, Error> {
+        // The packed return value only holds 16-bit positions; the out
+        // parameters hold the full range.
+        let (mut start, mut end) = (0u32, 0u32);
+        self.send_message(
+            EM_GETSEL,
+            WPARAM(&mut start as *mut u32 as usize),
+            LPARAM(&mut end as *mut u32 as isize),
+        )?;
+        // Safety: hwnd is valid until we call any user-provided callbacks
+        let text = get_window_text(&unsafe { RawHwnd::new(self.hwnd()) });
+        let end = (end as usize).min(text.len());
+        let start = (start as usize).min(end);
+        Ok(String::from_utf16_lossy(&text[start..end]))
+    }
+
+    fn replace_selection(&self, text: &str) -> Result<(), Error> {
+        let text = WideZString::new(text);
+        // wparam TRUE lets the user undo the replacement
+        self.send_message(EM_REPLACESEL, WPARAM(1), LPARAM(text.pzwstr().0 as isize))?;
+        Ok(())
+    }
+
+    fn line_count(&self) -> Result<u32, Error> {
+        self.check_multiline()?;
+        let count = self.send_message(EM_GETLINECOUNT, WPARAM(0), LPARAM(0))?;
+        Ok(count.0 as u32)
+    }
+
+    fn scroll_to_line(&self, line: u32) -> Result<(), Error> {
+        self.check_multiline()?;
+        // EM_LINESCROLL is relative to the current first visible line
+        let first = self.send_message(EM_GETFIRSTVISIBLELINE, WPARAM(0), LPARAM(0))?;
+        self.send_message(EM_LINESCROLL, WPARAM(0), LPARAM(line as isize - first.0))?;
+        Ok(())
+    }
+
+    fn set_text_limit(&self, chars: u32) -> Result<(), Error> {
+        self.send_message(EM_SETLIMITTEXT, WPARAM(chars as usize), LPARAM(0))?;
+        Ok(())
+    }
+}
+
+impl crate::ListBox<System> for ListBox {
+    fn add_string(&self, text: &str) -> Result<(), Error> {
+        let text = WideZString::new(text);
+        let result =
+            self.send_message(LB_ADDSTRING, WPARAM(0), LPARAM(text.pzwstr().0 as isize))?;
+        match result.0 as i32 {
+            LB_ERRSPACE => Err(windows::core::Error::from(E_OUTOFMEMORY).into()),
+            LB_ERR => Err(windows::core::Error::from(E_FAIL).into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl crate::UpDown<System> for UpDown {
+    fn set_buddy(&self, buddy: &Window) -> Result<(), Error> {
+        self.check_live()?;
+        buddy.check_live()?;
+        self.send_message(
+            UDM_SETBUDDY,
+            WPARAM(unsafe { buddy.hwnd() }.0 as usize),
+            LPARAM(0),
+        )?;
+        Ok(())
+    }
+
+    fn set_range(&self, min: i32, max: i32) -> Result<(), Error> {
+        self.send_message(UDM_SETRANGE32, WPARAM(min as usize), LPARAM(max as isize))?;
+        Ok(())
+    }
+
+    fn set_position(&self, position: i32) -> Result<(), Error> {
+        self.send_message(UDM_SETPOS32, WPARAM(0), LPARAM(position as isize))?;
+        Ok(())
+    }
+
+    fn on_value_changed<F: FnMut(i32) + 'static>(&self, callback: F) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_value_changed, Box::new(callback));
+        Ok(self)
+    }
+}
+
+impl TreeView {
+    fn check_item(&self, item: TreeItem) -> Result<HTREEITEM, Error> {
+        self.check_live()?;
+        if !self.callbacks.tree_items.borrow().contains(&item.0) {
+            return Err(Error::DeletedTreeItem);
+        }
+        Ok(HTREEITEM(item.0))
+    }
+}
+
+impl crate::TreeView<System> for TreeView {
+    fn insert_item(&self, parent: Option<TreeItem>, text: &str) -> Result<TreeItem, Error> {
+        let parent = match parent {
+            Some(parent) => self.check_item(parent)?,
+            None => {
+                self.check_live()?;
+                TVI_ROOT
+            }
+        };
+        let text = WideZString::new(text);
+        let insert = TVINSERTSTRUCTW {
+            hParent: parent,
+            hInsertAfter: TVI_LAST,
+            Anonymous: TVINSERTSTRUCTW_0 {
+                item: TVITEMW {
+                    mask: TVIF_TEXT,
+                    // TVM_INSERTITEMW copies the text without writing to it
+                    pszText: PWSTR(text.pzwstr().0 as *mut u16),
+                    ..Default::default()
+                },
+            },
+        };
+        // insert and text outlive the call
+        let item = self.send_message(
+            TVM_INSERTITEMW,
+            WPARAM(0),
+            LPARAM(&insert as *const _ as isize),
+        )?;
+        if item.0 == 0 {
+            return Err(windows::core::Error::from(E_OUTOFMEMORY).into());
+        }
+        self.callbacks.tree_items.borrow_mut().insert(item.0);
+        Ok(TreeItem(item.0))
+    }
+
+    fn delete_item(&self, item: TreeItem) -> Result<(), Error> {
+        let item = self.check_item(item)?;
+        // TVN_DELETEITEMW forgets item and its descendants
+        self.send_message(TVM_DELETEITEM, WPARAM(0), LPARAM(item.0))?;
+        Ok(())
+    }
+
+    fn expand(&self, item: TreeItem, expand: bool) -> Result<(), Error> {
+        let item = self.check_item(item)?;
+        let action = if expand { TVE_EXPAND } else { TVE_COLLAPSE };
+        self.send_message(TVM_EXPAND, WPARAM(action.0 as usize), LPARAM(item.0))?;
+        Ok(())
+    }
+
+    fn select(&self, item: TreeItem) -> Result<(), Error> {
+        let item = self.check_item(item)?;
+        self.send_message(TVM_SELECTITEM, WPARAM(TVGN_CARET as usize), LPARAM(item.0))?;
+        Ok(())
+    }
+
+    fn on_selection_changed<F: FnMut(TreeItem) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, Error> {
+        self.set_callback(&self.callbacks.on_selection_changed, Box::new(callback));
+        Ok(self)
+    }
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+    use crate::{Edit as _, ListBox as _, TreeView as _, UpDown as _, WindowSystem as _};
+    use std::{cell::Cell, rc::Rc};
+    use windows::Win32::UI::WindowsAndMessaging::{WM_LBUTTONDOWN, WM_LBUTTONUP};
+
+    // From WinUser.h, like MK_SHIFT and MK_CONTROL in comm_ctrl.rs
+    const MK_LBUTTON: usize = 0x0001;
+
+    fn multiline_edit() -> (Window, Edit) {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((400, 300)))
+            .unwrap();
+        let options = EditOptions {
+            multiline: true,
+            vscroll: true,
+            auto_vscroll: true,
+            ..Default::default()
+        };
+        let edit = main
+            .new_edit(options)
+            .unwrap()
+            .bounds(Some((0, 0)), Some((200, 100)))
+            .unwrap();
+        (main, edit)
+    }
+
+    #[test]
+    fn scroll_to_line() {
+        let (_main, edit) = multiline_edit();
+        let lines: Vec<_> = (0..100).map(|i| format!("Line {}", i)).collect();
+        let edit = edit.text(&lines.join("\r\n")).unwrap();
+        assert_eq!(edit.line_count().unwrap(), 100);
+        let before = edit.snapshot().unwrap();
+        edit.scroll_to_line(50).unwrap();
+        let first = edit
+            .send_message(EM_GETFIRSTVISIBLELINE, WPARAM(0), LPARAM(0))
+            .unwrap();
+        assert_eq!(first.0, 50);
+        let after = edit.snapshot().unwrap();
+        assert_eq!((before.width, before.height), (after.width, after.height));
+        assert_ne!(before.data, after.data);
+    }
+
+    #[test]
+    fn up_down_updates_buddy() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((400, 300)))
+            .unwrap();
+        let edit = main
+            .new_edit(Default::default())
+            .unwrap()
+            .bounds(Some((10, 10)), Some((80, 24)))
+            .unwrap();
+        let up_down = main
+            .new_up_down()
+            .unwrap()
+            .bounds(Some((90, 10)), Some((20, 24)))
+            .unwrap();
+        up_down.set_buddy(&edit).unwrap();
+        up_down.set_range(0, 10).unwrap();
+        up_down.set_position(3).unwrap();
+        assert_eq!(edit.get_text().unwrap(), "3");
+
+        let changed = Rc::new(Cell::new(None));
+        up_down
+            .on_value_changed({
+                let changed = changed.clone();
+                move |value| changed.set(Some(value))
+            })
+            .unwrap();
+
+        // Click the upper arrow
+        let (_, _, w, h) = get_client_rect(&unsafe { RawHwnd::new(up_down.hwnd()) }).unwrap();
+        let point = LPARAM((((h / 4) << 16) | (w / 2)) as isize);
+        up_down
+            .send_message(WM_LBUTTONDOWN, WPARAM(MK_LBUTTON), point)
+            .unwrap();
+        up_down
+            .send_message(WM_LBUTTONUP, WPARAM(0), point)
+            .unwrap();
+        assert_eq!(changed.get(), Some(4));
+        assert_eq!(edit.get_text().unwrap(), "4");
+    }
+
+    #[test]
+    fn text_limit() {
+        let (_main, edit) = multiline_edit();
+        let long = "x".repeat(40_000);
+        // EM_REPLACESEL honors the limit; SetWindowTextW doesn't
+        edit.replace_selection(&long).unwrap();
+        assert!(edit.get_text().unwrap().len() < long.len());
+
+        edit.set_text_limit(100_000).unwrap();
+        let edit = edit.text("").unwrap();
+        edit.replace_selection(&long).unwrap();
+        assert_eq!(edit.get_text().unwrap(), long);
+    }
+
+    #[test]
+    fn tree_view_select() {
+        let main = System::new()
+            .new_main()
+            .unwrap()
+            .bounds(None, Some((400, 300)))
+            .unwrap();
+        let tree = main
+            .new_tree_view()
+            .unwrap()
+            .bounds(Some((0, 0)), Some((200, 200)))
+            .unwrap();
+        let parent = tree.insert_item(None, "Parent").unwrap();
+        let child = tree.insert_item(Some(parent), "Child").unwrap();
+        tree.expand(parent, true).unwrap();
+
+        let selected = Rc::new(Cell::new(None));
+        tree.on_selection_changed({
+            let selected = selected.clone();
+            move |item| selected.set(Some(item))
+        })
+        .unwrap();
+        tree.select(child).unwrap();
+        assert_eq!(selected.get(), Some(child));
+        tree.select(parent).unwrap();
+        assert_eq!(selected.get(), Some(parent));
+    }
+
+    #[test]
+    fn add_string_after_destroy() {
+        let main = System::new().new_main().unwrap();
+        let list = main.new_list_box().unwrap();
+        list.add_string("Alive").unwrap();
+        list.destroy().unwrap();
+        super::super::pump_messages();
+        assert!(matches!(list.add_string("Gone"), Err(Error::Destroyed)));
+    }
+}
@@ -0,0 +1,109 @@
+// Common dialogs
+
+use std::{mem::size_of, path::PathBuf};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{Foundation::*, UI::Controls::Dialogs::*},
+};
+
+use super::{object_wrappers::*, Window};
+
+#[derive(Clone, Debug, Default)]
+pub struct FileDialogOptions {
+    /// (description, pattern), e.g. ("PNG", "*.png"). Separate multiple
+    /// patterns with `;`.
+    pub filters: Vec<(String, String)>,
+    pub initial_dir: Option<PathBuf>,
+    pub title: Option<String>,
+}
+
+/// Ask the user for an existing file. Returns None if they cancel. The dialog
+/// is modal to `parent` if given.
+pub fn open_file(
+    parent: Option<&Window>,
+    options: &FileDialogOptions,
+) -> Result<Option<PathBuf>, Error> {
+    run(
+        parent,
+        options,
+        OFN_EXPLORER | OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR,
+        GetOpenFileNameW,
+    )
+}
+
+/// Ask the user where to save a file, confirming overwrites. Returns None if
+/// they cancel. The dialog is modal to `parent` if given.
+pub fn save_file(
+    parent: Option<&Window>,
+    options: &FileDialogOptions,
+) -> Result<Option<PathBuf>, Error> {
+    run(
+        parent,
+        options,
+        OFN_EXPLORER | OFN_OVERWRITEPROMPT | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR,
+        GetSaveFileNameW,
+    )
+}
+
+fn run(
+    parent: Option<&Window>,
+    options: &FileDialogOptions,
+    flags: OPEN_FILENAME_FLAGS,
+    show: unsafe fn(*mut OPENFILENAMEW) -> BOOL,
+) -> Result<Option<PathBuf>, Error> {
+    let owner = match parent {
+        Some(parent) => {
+            parent.check_live()?;
+            unsafe { parent.hwnd() }
+        }
+        None => HWND(0),
+    };
+
+    // Pairs of null-terminated strings, ending with an extra null
+    let mut filter: Vec<u16> = Vec::new();
+    for (description, pattern) in &options.filters {
+        filter.extend(description.encode_utf16().chain([0]));
+        filter.extend(pattern.encode_utf16().chain([0]));
+    }
+    filter.push(0);
+
+    let initial_dir = options
+        .initial_dir
+        .as_ref()
+        .map(|dir| WideZString::new(&dir.to_string_lossy()));
+    let title = options.title.as_deref().map(WideZString::new);
+
+    // Large enough for long paths
+    let mut file = vec![0u16; 32768];
+
+    let mut ofn = OPENFILENAMEW {
+        lStructSize: size_of::<OPENFILENAMEW>() as u32,
+        hwndOwner: owner,
+        lpstrFilter: if options.filters.is_empty() {
+            PCWSTR::null()
+        } else {
+            PCWSTR(filter.as_ptr())
+        },
+        nFilterIndex: 1,
+        lpstrFile: PWSTR(file.as_mut_ptr()),
+        nMaxFile: file.len() as u32,
+        lpstrInitialDir: initial_dir
+            .as_ref()
+            .map_or(PCWSTR::null(), |dir| dir.pzwstr()),
+        lpstrTitle: title.as_ref().map_or(PCWSTR::null(), |t| t.pzwstr()),
+        Flags: flags,
+        ..Default::default()
+    };
+
+    // Safety: every buffer ofn points to outlives the modal call
+    if unsafe { show(&mut ofn) }.as_bool() {
+        let len = file.iter().position(|&c| c == 0).unwrap_or(file.len());
+        return Ok(Some(PathBuf::from(String::from_utf16_lossy(&file[..len]))));
+    }
+
+    // Zero means the user cancelled
+    match unsafe { CommDlgExtendedError() } {
+        COMMON_DLG_ERRORS(0) => Ok(None),
+        COMMON_DLG_ERRORS(code) => Err(Error::Dialog(code)),
+    }
+}
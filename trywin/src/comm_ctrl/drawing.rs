@@ -0,0 +1,35 @@
+//! GDI wrappers for drawing custom controls in
+//! [on_paint](super::WindowImpl::on_paint).
+//!
+//! ```no_run
+//! use trywin::{
+//!     comm_ctrl::{drawing::*, System},
+//!     ChildType, Color, Window, WindowSystem,
+//! };
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let system = System::new();
+//! let window = system.new_main()?;
+//!
+//! // A horizontal gauge which is 60% full
+//! let gauge = window
+//!     .new_child(ChildType::Custom(Default::default()))?
+//!     .bounds(Some((10, 10)), Some((200, 20)))?
+//!     .background(Color::rgb(40, 40, 40))?;
+//! let fill = 0.6;
+//! gauge.on_paint(move |dc| {
+//!     let Ok((x, y, w, h)) = get_client_rect(dc.hwnd()) else {
+//!         return;
+//!     };
+//!     if let Ok(brush) = HBrush::solid(Color::rgb(0, 200, 0)) {
+//!         fill_rect(dc, &brush, x, y, (w as f64 * fill) as i32, h);
+//!     }
+//! })?;
+//!
+//! window.visible(true)?;
+//! system.event_loop()?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use super::object_wrappers::{fill_rect, get_client_rect, HBrush, PaintDC, Raw, RawHwnd};
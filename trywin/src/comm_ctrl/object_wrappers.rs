@@ -8,7 +8,20 @@ use thiserror::Error;
 use windows::{
     core,
     core::*,
-    Win32::{Foundation::*, Graphics::Gdi::*, UI::WindowsAndMessaging::*},
+    Win32::{
+        Foundation::*,
+        Graphics::Gdi::*,
+        System::{
+            ApplicationInstallationAndServicing::{
+                ActivateActCtx, CreateActCtxW, DeactivateActCtx, ReleaseActCtx, ACTCTXW,
+            },
+            SystemInformation::GetSystemDirectoryW,
+        },
+        UI::{
+            Shell::{DragFinish, DragQueryFileW, HDROP},
+            WindowsAndMessaging::*,
+        },
+    },
 };
 
 use crate::Color;
@@ -21,8 +34,45 @@ pub enum Error {
     #[error("Window has been destroyed")]
     Destroyed,
 
+    #[error("Message-only windows have no display")]
+    MessageOnly,
+
+    #[error("Edit control is not multiline")]
+    NotMultiline,
+
+    #[error("Windows must be created and run on the thread which created the System")]
+    WrongThread,
+
+    #[error("Windows refused to bring the window to the foreground")]
+    ForegroundDenied,
+
+    #[error("Common dialog error {0:#x}")]
+    Dialog(u32),
+
+    #[error("Handle is not a window on this thread")]
+    NotAdoptable,
+
+    #[error("Only top-level windows support this")]
+    NotTopLevel,
+
+    #[error("Label and target must share a parent")]
+    DifferentParents,
+
+    #[error("Tree item has been deleted")]
+    DeletedTreeItem,
+
+    #[error("No monitor at index {0}")]
+    NoMonitor(usize),
+
     #[error("Unsupported bitmap format")]
     UnsupportedBitmapFormat,
+
+    #[error("Bitmap is {size} bytes, too small for {width}x{height} 32-bit pixels")]
+    BitmapSize {
+        width: usize,
+        height: usize,
+        size: usize,
+    },
 }
 
 pub struct WideZString(Vec<u16>);
@@ -78,6 +128,29 @@ mod raw_hwnd {
 }
 pub use raw_hwnd::RawHwnd;
 
+mod raw_hdc {
+    use super::*;
+    pub struct RawHdc(HDC);
+
+    impl RawHdc {
+        /// # Safety
+        ///
+        /// * Caller must ensure that the handle is valid for the lifetime of Self
+        /// * Caller must ensure that the handle is not null
+        pub unsafe fn new(hdc: HDC) -> Self {
+            Self(hdc)
+        }
+    }
+
+    impl Raw<HDC> for RawHdc {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HDC {
+            self.0
+        }
+    }
+}
+pub use raw_hdc::RawHdc;
+
 /// (x, y, w, h)
 pub fn get_window_rect(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Error> {
     let mut rect = RECT::default();
@@ -91,6 +164,45 @@ pub fn get_window_rect(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Er
     ))
 }
 
+/// (x, y, w, h) in the coordinate space [SetWindowPos] uses: screen
+/// coordinates for top-level windows and parent-client coordinates for
+/// child windows.
+pub fn get_window_bounds(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Error> {
+    let mut rect = RECT::default();
+    // Safety: raw() ensures hwnd is valid
+    unsafe {
+        GetWindowRect(hwnd.raw(), &mut rect)?;
+        let style = WINDOW_STYLE(GetWindowLongPtrW(hwnd.raw(), GWL_STYLE) as u32);
+        if style.contains(WS_CHILD) {
+            // Passing exactly two points makes MapWindowPoints treat them as a
+            // RECT, which keeps left <= right for mirrored parents.
+            let mut points = [
+                POINT {
+                    x: rect.left,
+                    y: rect.top,
+                },
+                POINT {
+                    x: rect.right,
+                    y: rect.bottom,
+                },
+            ];
+            MapWindowPoints(HWND(0), GetParent(hwnd.raw()), &mut points);
+            rect = RECT {
+                left: points[0].x,
+                top: points[0].y,
+                right: points[1].x,
+                bottom: points[1].y,
+            };
+        }
+    }
+    Ok((
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+    ))
+}
+
 /// (x, y, w, h)
 pub fn get_client_rect(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Error> {
     let mut rect = RECT::default();
@@ -104,6 +216,18 @@ pub fn get_client_rect(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Er
     ))
 }
 
+/// Window text as UTF-16, without the terminator
+pub fn get_window_text(hwnd: &impl Raw<HWND>) -> Vec<u16> {
+    // Safety: raw() ensures hwnd is valid
+    unsafe {
+        let len = GetWindowTextLengthW(hwnd.raw()).max(0) as usize;
+        let mut buf = vec![0u16; len + 1];
+        let copied = GetWindowTextW(hwnd.raw(), &mut buf).max(0) as usize;
+        buf.truncate(copied);
+        buf
+    }
+}
+
 mod window_dc {
     use super::*;
     pub struct WindowDC<'a, Hwnd: Raw<HWND>>(HDC, &'a Hwnd);
@@ -138,19 +262,114 @@ mod window_dc {
 }
 pub use window_dc::WindowDC;
 
+mod screen_dc {
+    use super::*;
+    pub struct ScreenDC(HDC);
+
+    impl ScreenDC {
+        pub fn new() -> Result<Self, Error> {
+            // Safety: a null HWND gets the DC for the whole screen
+            let hdc = unsafe { GetDC(None) };
+            if hdc.0 == 0 {
+                Err(core::Error::from_win32())?
+            }
+            Ok(Self(hdc))
+        }
+    }
+
+    impl Drop for ScreenDC {
+        fn drop(&mut self) {
+            // Safety: we ensure HDC is valid and unchanged.
+            unsafe {
+                ReleaseDC(None, self.0);
+            }
+        }
+    }
+
+    impl Raw<HDC> for ScreenDC {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HDC {
+            self.0
+        }
+    }
+}
+pub use screen_dc::ScreenDC;
+
+/// (x, y, w, h) of the work area, which excludes the taskbar, of the
+/// monitor holding most of the window
+pub fn monitor_work_area(hwnd: &impl Raw<HWND>) -> Result<(i32, i32, i32, i32), Error> {
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    // Safety: raw() ensures hwnd is valid
+    unsafe {
+        let monitor = MonitorFromWindow(hwnd.raw(), MONITOR_DEFAULTTONEAREST);
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            Err(core::Error::from_win32())?;
+        }
+    }
+    let r = info.rcWork;
+    Ok((r.left, r.top, r.right - r.left, r.bottom - r.top))
+}
+
+/// (x, y, w, h) of each monitor in virtual screen coordinates, in
+/// EnumDisplayMonitors order
+pub fn monitor_rects() -> Vec<(i32, i32, i32, i32)> {
+    unsafe extern "system" fn add(_: HMONITOR, _: HDC, rect: *mut RECT, data: LPARAM) -> BOOL {
+        // Safety: data is the Vec below, which outlives the enumeration
+        let rects = &mut *(data.0 as *mut Vec<(i32, i32, i32, i32)>);
+        let r = &*rect;
+        rects.push((r.left, r.top, r.right - r.left, r.bottom - r.top));
+        TRUE
+    }
+    let mut rects = Vec::new();
+    // Safety: add only runs during this call
+    unsafe {
+        EnumDisplayMonitors(
+            HDC(0),
+            None,
+            Some(add),
+            LPARAM(&mut rects as *mut Vec<_> as isize),
+        );
+    }
+    rects
+}
+
 mod paint_dc {
     use super::*;
 
-    pub struct PaintDC<'a, Hwnd: Raw<HWND>>(HDC, &'a Hwnd);
+    /// BeginPaint must be paired with exactly one EndPaint, which must receive
+    /// the same PAINTSTRUCT; otherwise the update region is never validated
+    /// and the window repaints forever. Holding the PAINTSTRUCT here and
+    /// calling EndPaint from drop guarantees both, even if drawing panics.
+    pub struct PaintDC<'a, Hwnd: Raw<HWND>>(PAINTSTRUCT, &'a Hwnd);
 
     impl<'a, Hwnd: Raw<HWND>> PaintDC<'a, Hwnd> {
         pub fn new(hwnd: &'a Hwnd) -> Result<Self, Error> {
+            let mut ps = PAINTSTRUCT::default();
             // Safety: Self holds a ref to Hwnd, ensuring its lifetime
-            let hdc = unsafe { BeginPaint(hwnd.raw(), &mut PAINTSTRUCT::default()) };
+            let hdc = unsafe { BeginPaint(hwnd.raw(), &mut ps) };
             if hdc.0 == 0 {
                 Err(core::Error::from_win32())?
             }
-            Ok(Self(hdc, hwnd))
+            Ok(Self(ps, hwnd))
+        }
+
+        /// The window being painted
+        pub fn hwnd(&self) -> &'a Hwnd {
+            self.1
+        }
+
+        /// (x, y, w, h) of the area which needs painting
+        pub fn paint_rect(&self) -> (i32, i32, i32, i32) {
+            let rect = self.0.rcPaint;
+            (
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+            )
         }
     }
 
@@ -158,7 +377,7 @@ mod paint_dc {
         fn drop(&mut self) {
             // Safety: HDC::raw() ensures HWND is valid and unchanged
             unsafe {
-                EndPaint(self.1.raw(), &PAINTSTRUCT::default());
+                EndPaint(self.1.raw(), &self.0);
             }
         }
     }
@@ -166,7 +385,7 @@ mod paint_dc {
     impl<'a, Hwnd: Raw<HWND>> Raw<HDC> for PaintDC<'a, Hwnd> {
         // Safety: see Raw::raw()
         unsafe fn raw(&self) -> HDC {
-            self.0
+            self.0.hdc
         }
     }
 }
@@ -260,6 +479,40 @@ pub fn select_object<'a, R, DC: Raw<HDC>, Obj: Raw<HGDIOBJ>, F: FnOnce() -> Resu
     f()
 }
 
+/// (width, height) of text in the window's current font. Lines split on
+/// `\n`; width is the widest line and height sums the line heights.
+pub fn measure_text(hwnd: &impl Raw<HWND>, text: &str) -> Result<(i32, i32), Error> {
+    let dc = WindowDC::new(hwnd)?;
+    let measure = || -> Result<(i32, i32), Error> {
+        let mut metrics = TEXTMETRICW::default();
+        // Safety: dc.raw() ensures HDC is valid
+        unsafe { GetTextMetricsW(dc.raw(), &mut metrics) }.ok()?;
+        let (mut width, mut lines) = (0, 0);
+        for line in text.split('\n') {
+            let line: Vec<u16> = line.trim_end_matches('\r').encode_utf16().collect();
+            let mut size = SIZE::default();
+            // Safety: dc.raw() ensures HDC is valid
+            unsafe { GetTextExtentPoint32W(dc.raw(), &line, &mut size) }.ok()?;
+            width = width.max(size.cx);
+            lines += 1;
+        }
+        Ok((width, lines * metrics.tmHeight))
+    };
+
+    // Not WindowImpl::send_message, whose liveness check guards handles
+    // which may have been destroyed: Raw's contract already guarantees hwnd
+    // is valid, and this also measures for windows we don't wrap.
+    // Safety: raw() ensures hwnd is valid. Null means the system font, which
+    //         a fresh DC already has selected.
+    let font = unsafe { SendMessageW(hwnd.raw(), WM_GETFONT, WPARAM(0), LPARAM(0)) };
+    if font.0 == 0 {
+        return measure();
+    }
+    // Safety: the window keeps its font alive at least as long as hwnd
+    let font = unsafe { BorrowedGdiobj::new(hwnd, HGDIOBJ(font.0)) };
+    select_object(&dc, &font, measure)
+}
+
 mod hbitmap {
     use super::*;
     pub struct HBitmap(HBITMAP);
@@ -275,6 +528,13 @@ mod hbitmap {
             Ok(bm)
         }
 
+        /// # Safety
+        ///
+        /// `bm` must be a valid bitmap which nothing else will delete.
+        pub unsafe fn from_raw(bm: HBITMAP) -> Self {
+            HBitmap(bm)
+        }
+
         pub fn gdiobj(&self) -> BorrowedGdiobj<Self> {
             // Safety: we ensure HBITMAP/HGDIOBJ is valid for our lifetime.
             unsafe { BorrowedGdiobj::new(self, HGDIOBJ(self.0 .0)) }
@@ -299,6 +559,93 @@ mod hbitmap {
 }
 pub use hbitmap::HBitmap;
 
+mod dib_section {
+    use super::*;
+
+    /// Top-down 32-bit DIB section. Pixels are 0xAARRGGBB.
+    pub struct DibSection(HBitmap, *mut u32, usize);
+
+    impl DibSection {
+        pub fn new<DC: Raw<HDC>>(dc: &DC, width: i32, height: i32) -> Result<Self, Error> {
+            let bmi = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as _,
+                    biWidth: width,
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                bmiColors: Default::default(),
+            };
+            let mut bits = std::ptr::null_mut();
+            // Safety: dc.raw() ensures HDC is valid. The bits belong to the
+            //         bitmap, which HBitmap keeps alive for our lifetime.
+            let bm = unsafe {
+                HBitmap::from_raw(CreateDIBSection(
+                    dc.raw(),
+                    &bmi,
+                    DIB_RGB_COLORS,
+                    &mut bits,
+                    None,
+                    0,
+                )?)
+            };
+            Ok(Self(bm, bits as *mut u32, (width * height) as usize))
+        }
+
+        pub fn pixels_mut(&mut self) -> &mut [u32] {
+            // Safety: the bits live as long as the bitmap
+            unsafe { std::slice::from_raw_parts_mut(self.1, self.2) }
+        }
+
+        pub fn gdiobj(&self) -> BorrowedGdiobj<'_, HBitmap> {
+            self.0.gdiobj()
+        }
+    }
+}
+pub use dib_section::DibSection;
+
+/// Replace a layered window's whole content, including any frame, with a
+/// translucent color
+pub fn update_layered(hwnd: &impl Raw<HWND>, color: Color) -> Result<(), Error> {
+    let (x, y, w, h) = get_window_rect(hwnd)?;
+    let window_dc = WindowDC::new(hwnd)?;
+    let memory_dc = MemoryDc::compatible(&window_dc)?;
+    let mut dib = DibSection::new(&memory_dc, w, h)?;
+
+    // UpdateLayeredWindow wants premultiplied alpha
+    let Color(r, g, b, a) = color;
+    let premultiply = |c: u8| c as u32 * a as u32 / 255;
+    dib.pixels_mut()
+        .fill((a as u32) << 24 | premultiply(r) << 16 | premultiply(g) << 8 | premultiply(b));
+
+    select_object(&memory_dc, &dib.gdiobj(), || {
+        let blend = BLENDFUNCTION {
+            BlendOp: AC_SRC_OVER as u8,
+            BlendFlags: 0,
+            SourceConstantAlpha: 255,
+            AlphaFormat: AC_SRC_ALPHA as u8,
+        };
+        // Safety: hwnd.raw() and memory_dc.raw() ensure HWND and HDC are valid.
+        unsafe {
+            UpdateLayeredWindow(
+                hwnd.raw(),
+                None,
+                Some(&POINT { x, y }),
+                Some(&SIZE { cx: w, cy: h }),
+                memory_dc.raw(),
+                Some(&POINT::default()),
+                COLORREF(0),
+                Some(&blend),
+                ULW_ALPHA,
+            )?;
+        }
+        Ok(())
+    })
+}
+
 mod hbrush {
     use super::*;
 
@@ -307,11 +654,7 @@ mod hbrush {
     impl HBrush {
         pub fn solid(color: Color) -> Result<Self, Error> {
             // Safety: we ensure HBRUSH is valid.
-            let brush = unsafe {
-                HBrush(CreateSolidBrush(COLORREF(
-                    (color.0 as u32) | ((color.1 as u32) << 8) | ((color.2 as u32) << 16),
-                )))
-            };
+            let brush = unsafe { HBrush(CreateSolidBrush(COLORREF(color.to_colorref()))) };
             if brush.0 .0 == 0 {
                 Err(core::Error::from_win32())?
             }
@@ -337,6 +680,347 @@ mod hbrush {
 }
 pub use hbrush::*;
 
+mod hrgn {
+    use super::*;
+
+    pub struct HRgn(HRGN);
+
+    impl HRgn {
+        pub fn rect(x: i32, y: i32, w: i32, h: i32) -> Result<Self, Error> {
+            // Safety: we ensure HRGN is valid.
+            Self::checked(unsafe { CreateRectRgn(x, y, x + w, y + h) })
+        }
+
+        /// `w` by `h` at the origin, with corners rounded by an ellipse of
+        /// `ellipse_w` by `ellipse_h`
+        pub fn round_rect(w: i32, h: i32, ellipse_w: i32, ellipse_h: i32) -> Result<Self, Error> {
+            // Rounded regions exclude their right and bottom edges
+            // Safety: we ensure HRGN is valid.
+            Self::checked(unsafe { CreateRoundRectRgn(0, 0, w + 1, h + 1, ellipse_w, ellipse_h) })
+        }
+
+        fn checked(rgn: HRGN) -> Result<Self, Error> {
+            if rgn.0 == 0 {
+                Err(core::Error::from_win32())?
+            }
+            Ok(HRgn(rgn))
+        }
+
+        /// Add `other`'s area to this region
+        pub fn union(&mut self, other: &HRgn) -> Result<(), Error> {
+            // Safety: we ensure both HRGNs are valid. Dest may alias a source.
+            if unsafe { CombineRgn(self.0, self.0, other.0, RGN_OR) } == RGN_ERROR {
+                Err(core::Error::from_win32())?
+            }
+            Ok(())
+        }
+
+        /// Clip `hwnd` to this region. The system owns the region once this
+        /// succeeds, so it mustn't be deleted; on failure it's deleted here.
+        pub fn set_window_region(self, hwnd: &RawHwnd) -> Result<(), Error> {
+            let rgn = std::mem::ManuallyDrop::new(self);
+            // Safety: hwnd.raw() ensures HWND is valid; we ensure HRGN is.
+            if unsafe { SetWindowRgn(hwnd.raw(), rgn.0, TRUE) } == 0 {
+                let err = core::Error::from_win32();
+                drop(std::mem::ManuallyDrop::into_inner(rgn));
+                Err(err)?
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for HRgn {
+        fn drop(&mut self) {
+            // Safety: we ensure HRGN is valid, and set_window_region doesn't
+            //         drop one the system now owns.
+            unsafe {
+                DeleteObject(self.0);
+            }
+        }
+    }
+
+    impl Raw<HRGN> for HRgn {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HRGN {
+            self.0
+        }
+    }
+}
+pub use hrgn::*;
+
+mod hfont {
+    use super::*;
+
+    pub struct HFont(HFONT);
+
+    impl HFont {
+        /// Regular weight font. Family names longer than 31 characters are
+        /// truncated.
+        pub fn new(family: &str, point_size: i32) -> Result<Self, Error> {
+            let screen_dc = ScreenDC::new()?;
+            // Safety: screen_dc.raw() ensures HDC is valid
+            let dpi = unsafe { GetDeviceCaps(screen_dc.raw(), LOGPIXELSY) };
+            let mut lf = LOGFONTW {
+                lfHeight: -(point_size * dpi + 36) / 72,
+                lfWeight: FW_NORMAL.0 as i32,
+                lfCharSet: DEFAULT_CHARSET,
+                lfQuality: CLEARTYPE_QUALITY,
+                ..Default::default()
+            };
+            for (dest, src) in lf.lfFaceName[..31].iter_mut().zip(family.encode_utf16()) {
+                *dest = src;
+            }
+            Self::indirect(&lf)
+        }
+
+        /// The font Windows uses in message boxes, e.g. Segoe UI
+        pub fn message_font() -> Result<Self, Error> {
+            Self::indirect(&Self::message_logfont()?)
+        }
+
+        /// Description of `message_font`, which changes when the user
+        /// changes display settings
+        pub fn message_logfont() -> Result<LOGFONTW, Error> {
+            let mut metrics = NONCLIENTMETRICSW {
+                cbSize: std::mem::size_of::<NONCLIENTMETRICSW>() as _,
+                ..Default::default()
+            };
+            // Safety: metrics is large enough for SPI_GETNONCLIENTMETRICS
+            unsafe {
+                SystemParametersInfoW(
+                    SPI_GETNONCLIENTMETRICS,
+                    metrics.cbSize,
+                    Some(&mut metrics as *mut _ as *mut _),
+                    Default::default(),
+                )?;
+            }
+            Ok(metrics.lfMessageFont)
+        }
+
+        pub fn indirect(lf: &LOGFONTW) -> Result<Self, Error> {
+            // Safety: we ensure HFONT is valid.
+            let font = unsafe { HFont(CreateFontIndirectW(lf)) };
+            if font.0 .0 == 0 {
+                Err(core::Error::from_win32())?
+            }
+            Ok(font)
+        }
+    }
+
+    impl Drop for HFont {
+        fn drop(&mut self) {
+            // Safety: we ensure HFONT is valid.
+            unsafe {
+                DeleteObject(self.0);
+            }
+        }
+    }
+
+    impl Raw<HFONT> for HFont {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HFONT {
+            self.0
+        }
+    }
+}
+pub use hfont::*;
+
+mod haccel {
+    use super::*;
+
+    pub struct HAccel(HACCEL);
+
+    impl HAccel {
+        pub fn new(accels: &[ACCEL]) -> Result<Self, Error> {
+            // Safety: we ensure HACCEL is valid.
+            unsafe { Ok(HAccel(CreateAcceleratorTableW(accels)?)) }
+        }
+    }
+
+    impl Drop for HAccel {
+        fn drop(&mut self) {
+            // Safety: we ensure HACCEL is valid.
+            unsafe {
+                DestroyAcceleratorTable(self.0);
+            }
+        }
+    }
+
+    impl Raw<HACCEL> for HAccel {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HACCEL {
+            self.0
+        }
+    }
+}
+pub use haccel::*;
+
+mod act_ctx {
+    use super::*;
+    use std::marker::PhantomData;
+
+    // From WinBase.h
+    const ACTCTX_FLAG_ASSEMBLY_DIRECTORY_VALID: u32 = 0x4;
+    const ACTCTX_FLAG_RESOURCE_NAME_VALID: u32 = 0x8;
+
+    // shell32.dll embeds a manifest which depends on comctl32 v6
+    const SHELL32_MANIFEST_ID: usize = 124;
+
+    pub struct ActCtx(HANDLE);
+
+    impl ActCtx {
+        /// Activation context selecting comctl32 v6, which draws controls
+        /// with visual styles
+        pub fn common_controls_v6() -> Result<Self, Error> {
+            let mut dir = [0u16; 260];
+            // Safety: dir is a valid buffer
+            let len = unsafe { GetSystemDirectoryW(Some(&mut dir)) } as usize;
+            if len == 0 || len >= dir.len() {
+                Err(core::Error::from_win32())?
+            }
+            let ctx = ACTCTXW {
+                cbSize: std::mem::size_of::<ACTCTXW>() as u32,
+                dwFlags: ACTCTX_FLAG_ASSEMBLY_DIRECTORY_VALID | ACTCTX_FLAG_RESOURCE_NAME_VALID,
+                lpSource: w!("shell32.dll"),
+                lpAssemblyDirectory: PCWSTR(dir.as_ptr()),
+                lpResourceName: PCWSTR(SHELL32_MANIFEST_ID as *const u16),
+                ..Default::default()
+            };
+            // Safety: the strings outlive the call. We ensure HANDLE is valid.
+            Ok(ActCtx(unsafe { CreateActCtxW(&ctx)? }))
+        }
+
+        /// Make this the thread's active context until the result drops
+        pub fn activate(&self) -> Result<Activation<'_>, Error> {
+            let mut cookie = 0;
+            // Safety: we ensure HANDLE is valid.
+            unsafe { ActivateActCtx(self.0, &mut cookie)? };
+            Ok(Activation(PhantomData, cookie))
+        }
+    }
+
+    impl Drop for ActCtx {
+        fn drop(&mut self) {
+            // Safety: we ensure HANDLE is valid.
+            unsafe {
+                ReleaseActCtx(self.0);
+            }
+        }
+    }
+
+    // Borrows the context so it can't be released while active
+    pub struct Activation<'a>(PhantomData<&'a ActCtx>, usize);
+
+    impl<'a> Drop for Activation<'a> {
+        fn drop(&mut self) {
+            // Safety: the cookie came from ActivateActCtx on this thread, and
+            //         activations nest, so they drop in reverse order.
+            unsafe {
+                let _ = DeactivateActCtx(0, self.1);
+            }
+        }
+    }
+}
+pub use act_ctx::*;
+
+mod hdrop {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Files from WM_DROPFILES. Dropping this calls `DragFinish` exactly
+    /// once, even if a callback using the files panics.
+    pub struct HDrop(HDROP);
+
+    impl HDrop {
+        /// # Safety
+        ///
+        /// `hdrop` must be the wparam of a WM_DROPFILES which nothing else
+        /// will finish.
+        pub unsafe fn new(hdrop: HDROP) -> Self {
+            HDrop(hdrop)
+        }
+
+        pub fn files(&self) -> Vec<PathBuf> {
+            // Safety: we ensure HDROP is valid.
+            unsafe {
+                let count = DragQueryFileW(self.0, u32::MAX, None);
+                (0..count)
+                    .map(|i| {
+                        let len = DragQueryFileW(self.0, i, None);
+                        let mut buf = vec![0u16; len as usize + 1];
+                        let len = DragQueryFileW(self.0, i, Some(&mut buf));
+                        PathBuf::from(String::from_utf16_lossy(&buf[..len as usize]))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    impl Drop for HDrop {
+        fn drop(&mut self) {
+            // Safety: we ensure HDROP is valid.
+            unsafe {
+                DragFinish(self.0);
+            }
+        }
+    }
+}
+pub use hdrop::*;
+
+#[cfg(feature = "async")]
+mod wake_event {
+    use super::*;
+    use std::{sync::Arc, task::Wake};
+    use windows::Win32::System::Threading::{CreateEventW, SetEvent};
+
+    /// Auto-reset event. Doubles as a waker which signals it.
+    pub struct WakeEvent(HANDLE);
+
+    // Safety: event handles may be signaled and closed from any thread
+    unsafe impl Send for WakeEvent {}
+    unsafe impl Sync for WakeEvent {}
+
+    impl WakeEvent {
+        pub fn new() -> Result<Self, Error> {
+            // Safety: we ensure HANDLE is valid.
+            unsafe { Ok(WakeEvent(CreateEventW(None, false, false, None)?)) }
+        }
+
+        pub fn set(&self) {
+            // Safety: we ensure HANDLE is valid. Fails only on bad handles.
+            unsafe { _ = SetEvent(self.0) };
+        }
+    }
+
+    impl Wake for WakeEvent {
+        fn wake(self: Arc<Self>) {
+            self.set();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.set();
+        }
+    }
+
+    impl Drop for WakeEvent {
+        fn drop(&mut self) {
+            // Safety: we ensure HANDLE is valid.
+            unsafe {
+                _ = CloseHandle(self.0);
+            }
+        }
+    }
+
+    impl Raw<HANDLE> for WakeEvent {
+        // Safety: see Raw::raw()
+        unsafe fn raw(&self) -> HANDLE {
+            self.0
+        }
+    }
+}
+#[cfg(feature = "async")]
+pub use wake_event::*;
+
 pub fn fill_rect<'a, DC: Raw<HDC>, Brush: Raw<HBRUSH>>(
     dc: &'a DC,
     brush: &'a Brush,
@@ -359,3 +1043,44 @@ pub fn fill_rect<'a, DC: Raw<HDC>, Brush: Raw<HBRUSH>>(
         );
     }
 }
+
+#[cfg(all(test, windows))]
+mod tests {
+    use crate::{comm_ctrl::System, ChildType, Window as _, WindowSystem as _};
+
+    #[test]
+    fn resize_keeps_child_position() {
+        let system = System::new();
+        let main = system
+            .new_main()
+            .unwrap()
+            .bounds(Some((100, 100)), Some((400, 300)))
+            .unwrap();
+        let child = main
+            .new_child(ChildType::Button)
+            .unwrap()
+            .bounds(Some((30, 40)), Some((80, 20)))
+            .unwrap();
+        // A screen-coordinate round trip would move the child on each call
+        let child = child.bounds(None, Some((90, 25))).unwrap();
+        let child = child.bounds(None, Some((90, 25))).unwrap();
+        assert_eq!(child.get_bounds().unwrap(), (30, 40, 90, 25));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let main = System::new().new_main().unwrap();
+        // A surrogate pair and a combining acute accent
+        let text = "\u{1F600} cafe\u{301}";
+        let edit = main
+            .new_child(ChildType::Edit(Default::default()))
+            .unwrap()
+            .bounds(Some((0, 0)), Some((200, 20)))
+            .unwrap()
+            .text(text)
+            .unwrap();
+        assert_eq!(edit.get_text().unwrap(), text);
+        let main = main.title(text).unwrap();
+        assert_eq!(main.get_title().unwrap(), text);
+    }
+}
@@ -6,6 +6,10 @@ use std::{
     process::abort,
     rc::Rc,
     result::Result,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once, OnceLock,
+    },
 };
 use windows::{
     core,
@@ -20,15 +24,71 @@ use windows::{
 use super::object_wrappers::Error;
 use crate::comm_ctrl::object_wrappers::WideZString;
 
+/// This executable's module handle. It never changes, so it's looked up
+/// once per process.
+pub fn module_instance() -> Result<HMODULE, Error> {
+    static INSTANCE: OnceLock<HMODULE> = OnceLock::new();
+    if let Some(instance) = INSTANCE.get() {
+        return Ok(*instance);
+    }
+    // Safety: None asks for the calling executable's handle
+    let instance = unsafe { GetModuleHandleA(None)? };
+    Ok(*INSTANCE.get_or_init(|| instance))
+}
+
 mod created_window {
     use super::*;
 
-    pub struct CreatedWindow(Rc<Cell<HWND>>);
+    const WINDOW_CLASS: PCWSTR = w!("general_window");
+
+    // Set once WINDOW_CLASS is known to be registered, so later creations
+    // skip the GetClassInfoExW probe. Classes are per-process, so this is too.
+    static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    unsafe fn register_class(instance: HMODULE) -> Result<(), Error> {
+        if REGISTERED.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        if GetClassInfoExW(instance, WINDOW_CLASS, &mut WNDCLASSEXW::default()).is_err() {
+            let atom = RegisterClassExW(&WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                // CS_DBLCLKS for on_double_click; without it Windows sends
+                // a second WM_LBUTTONDOWN instead
+                style: CS_HREDRAW | CS_VREDRAW | CS_DBLCLKS,
+                lpfnWndProc: Some(static_wndproc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: instance.into(),
+                hIcon: Default::default(),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                hbrBackground: Default::default(),
+                lpszMenuName: PCWSTR::null(),
+                lpszClassName: WINDOW_CLASS,
+                hIconSm: Default::default(),
+            });
+            if atom == 0 {
+                let err = core::Error::from_win32();
+                // Another thread may have won the race; that's fine
+                if err.code() != ERROR_CLASS_ALREADY_EXISTS.to_hresult() {
+                    Err(err)?;
+                }
+            }
+        }
+        REGISTERED.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub struct CreatedWindow {
+        hwnd: Rc<Cell<HWND>>,
+        owned: bool, // False for adopted windows; see CreatedWindow::adopt
+    }
 
     impl CreatedWindow {
         /// # Safety
         /// * `parent` must either be valid or null.
         /// * If `control_class` is Some, then it must be a comctl32 class.
+        /// * `control_id` must be None for top-level windows, where the same
+        ///   argument of CreateWindowExW is a menu handle.
         /// * The class name "general_window" is reserved for use by this function.
         //
         // TODO: better name for "general_window" that's less likely to clash.
@@ -39,40 +99,26 @@ mod created_window {
             window_ex_style: WINDOW_EX_STYLE,
             parent: HWND,
             control_class: Option<&str>,
+            control_id: Option<u16>,
             x: Option<i32>,
             y: Option<i32>,
             w: Option<i32>,
             h: Option<i32>,
         ) -> Result<Self, Error> {
-            const WINDOW_CLASS: PCWSTR = w!("general_window");
-
-            let instance = GetModuleHandleA(None)?;
+            let instance = module_instance()?;
 
             if control_class.is_some() {
-                // TODO: add more flags
-                InitCommonControlsEx(&INITCOMMONCONTROLSEX {
-                    dwSize: size_of::<INITCOMMONCONTROLSEX>() as u32,
-                    dwICC: ICC_STANDARD_CLASSES,
-                });
-            } else if GetClassInfoExW(instance, WINDOW_CLASS, &mut WNDCLASSEXW::default()).is_err()
-            {
-                let atom = RegisterClassExW(&WNDCLASSEXW {
-                    cbSize: size_of::<WNDCLASSEXW>() as u32,
-                    style: CS_HREDRAW | CS_VREDRAW,
-                    lpfnWndProc: Some(static_wndproc),
-                    cbClsExtra: 0,
-                    cbWndExtra: 0,
-                    hInstance: instance.into(),
-                    hIcon: Default::default(),
-                    hCursor: Default::default(),
-                    hbrBackground: Default::default(),
-                    lpszMenuName: PCWSTR::null(),
-                    lpszClassName: WINDOW_CLASS,
-                    hIconSm: Default::default(),
+                // Process-wide; once is enough
+                static COMMON_CONTROLS: Once = Once::new();
+                COMMON_CONTROLS.call_once(|| {
+                    // TODO: add more flags
+                    InitCommonControlsEx(&INITCOMMONCONTROLSEX {
+                        dwSize: size_of::<INITCOMMONCONTROLSEX>() as u32,
+                        dwICC: ICC_STANDARD_CLASSES | ICC_UPDOWN_CLASS | ICC_TREEVIEW_CLASSES,
+                    });
                 });
-                if atom == 0 {
-                    Err(core::Error::from_win32())?;
-                }
+            } else {
+                register_class(instance)?;
             }
 
             let hwnd = Rc::new(Cell::new(HWND(0)));
@@ -93,7 +139,7 @@ mod created_window {
                 w.unwrap_or(CW_USEDEFAULT),
                 h.unwrap_or(CW_USEDEFAULT),
                 parent,
-                None,
+                HMENU(control_id.map_or(0, |id| id as isize)),
                 instance,
                 if control_class.is_some() {
                     None
@@ -102,20 +148,56 @@ mod created_window {
                 },
             );
             if created_hwnd == Default::default() {
-                Err(core::Error::from_win32())?;
+                let err = core::Error::from_win32();
+                // Something unregistered the class; probe again next time
+                if err.code() == ERROR_CANNOT_FIND_WND_CLASS.to_hresult() {
+                    REGISTERED.store(false, Ordering::Release);
+                }
+                Err(err)?;
             }
 
             if control_class.is_some() {
+                // Without the subclass, nothing would null hwnd on
+                // WM_NCDESTROY and drop would destroy a stale handle
+                let p = state.take().unwrap();
+                if !SetWindowSubclass(created_hwnd, Some(static_subclass_wndproc), 0, p as usize)
+                    .as_bool()
+                {
+                    let err = core::Error::from_win32();
+                    drop(Box::from_raw(p as *mut StaticWndprocState));
+                    let _ = DestroyWindow(created_hwnd);
+                    Err(err)?;
+                }
                 hwnd.replace(created_hwnd);
-                SetWindowSubclass(
-                    created_hwnd,
-                    Some(static_subclass_wndproc),
-                    0,
-                    state.take().unwrap() as usize,
-                );
             }
 
-            Ok(Self(hwnd))
+            Ok(Self { hwnd, owned: true })
+        }
+
+        /// Attach `window_proc` to a window created elsewhere, as subclass 0
+        /// using `static_subclass_wndproc`. Dropping the result detaches
+        /// `window_proc` instead of destroying the window.
+        ///
+        /// # Safety
+        /// * `hwnd` must be valid and belong to the calling thread.
+        /// * `hwnd` must not already have subclass 0 of
+        ///   `static_subclass_wndproc`; i.e. it must not be one of ours.
+        pub unsafe fn adopt<T: WindowProc + 'static>(
+            window_proc: T,
+            hwnd: HWND,
+        ) -> Result<Self, Error> {
+            let cell = Rc::new(Cell::new(hwnd));
+            let state = StaticWndprocState::new(cell.clone(), window_proc);
+            let state = Box::into_raw(Box::new(state));
+            if !SetWindowSubclass(hwnd, Some(static_subclass_wndproc), 0, state as usize).as_bool()
+            {
+                drop(Box::from_raw(state));
+                Err(core::Error::from_win32())?;
+            }
+            Ok(Self {
+                hwnd: cell,
+                owned: false,
+            })
         }
 
         /// # Safety
@@ -125,19 +207,41 @@ mod created_window {
         /// * Callers must not use hwnd after it is destroyed.
         /// * Callers may destroy hwnd.
         pub unsafe fn hwnd(&self) -> HWND {
-            self.0.get()
+            self.hwnd.get()
+        }
+
+        /// False if the window was adopted rather than created
+        pub fn owned(&self) -> bool {
+            self.owned
         }
     }
 
+    // The StaticWndprocState shares the hwnd cell, and nulls it on
+    // WM_NCDESTROY before freeing itself, so a destroyed window never
+    // reaches DestroyWindow here.
     impl Drop for CreatedWindow {
         fn drop(&mut self) {
-            let hwnd = self.0.get();
-            if hwnd != HWND(0) {
-                // Safety: self.0 is valid. Caller of OwnedWindow::new
-                //         is responsible for setting hwnd to null.
+            let hwnd = self.hwnd.get();
+            if hwnd == HWND(0) {
+                return;
+            }
+            if !self.owned {
+                // Safety: hwnd is valid until it's null. Only adopt
+                //         attaches subclass 0 to an unowned window.
                 unsafe {
-                    let _ = DestroyWindow(hwnd);
+                    let mut p = 0;
+                    if GetWindowSubclass(hwnd, Some(static_subclass_wndproc), 0, Some(&mut p))
+                        .as_bool()
+                    {
+                        detach_subclass(hwnd, p as *const StaticWndprocState);
+                    }
                 }
+                return;
+            }
+            // Safety: self.hwnd is valid. Caller of OwnedWindow::new
+            //         is responsible for setting hwnd to null.
+            unsafe {
+                let _ = DestroyWindow(hwnd);
             }
         }
     }
@@ -162,6 +266,8 @@ pub mod window_proc {
         ///   may destroy the HWND directly or indirectly, e.g. by destroying
         ///   a parent HWND. Both caller and implementer need to safely handle this.
         /// * Caller must not pass an invalid HWND to `default`.
+        /// * `default` is a trait object rather than a generic so that `WindowProc`
+        ///   stays object safe; implementers may wrap it in additional logic.
         unsafe fn wndproc(
             &self,
             commctrl: bool,
@@ -169,7 +275,7 @@ pub mod window_proc {
             message: u32,
             wparam: WPARAM,
             lparam: LPARAM,
-            default: fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
+            default: &dyn Fn(HWND, u32, WPARAM, LPARAM) -> LRESULT,
         ) -> LRESULT;
     }
 
@@ -208,7 +314,8 @@ pub mod window_proc {
     ///   `[Box::into_raw]`. `static_wndproc` owns `p` and will eventually
     ///   release it using `drop(Box::from_raw(p))`. `[Box::into_raw]` must
     ///   have been called on the same thread which created HWND.
-    /// * `CREATESTRUCTW::lpCreateParams` must be `p`; it can't be null.
+    /// * `CREATESTRUCTW::lpCreateParams` must be `p`. If it's null, the window
+    ///   only gets default handling.
     /// * `GWLP_USERDATA` must either be null or be `p`.
     /// * Must only be called by the Windows API.
     /// * The Windows API guarantees that it will only call this function in the
@@ -219,10 +326,17 @@ pub mod window_proc {
         wparam: WPARAM,
         lparam: LPARAM,
     ) -> LRESULT {
-        // Get p or immediately return if it's null.
+        // Get p or immediately return if it's null. Some messages arrive
+        // before WM_NCCREATE, most notably WM_GETMINMAXINFO; p isn't
+        // available yet, so they get default handling. Anything which needs
+        // to customize those (e.g. min/max size) must be reapplied after
+        // creation.
         let p: *const StaticWndprocState;
         if message == WM_NCCREATE {
             p = (*(lparam.0 as *const CREATESTRUCTW)).lpCreateParams as *const StaticWndprocState;
+            if p.is_null() {
+                return DefWindowProcW(handle, message, wparam, lparam);
+            }
             SetWindowLongPtrW(handle, GWLP_USERDATA, p as isize);
             // Safety: hwnd never changes once set, except back to null.
             if (*p).hwnd.get() == HWND(0) {
@@ -230,6 +344,8 @@ pub mod window_proc {
             }
         } else {
             p = GetWindowLongPtrW(handle, GWLP_USERDATA) as *const StaticWndprocState;
+            // Null until WM_NCCREATE stores it. WM_GETMINMAXINFO, and possibly
+            // WM_NCCALCSIZE, can arrive before that.
             if p.is_null() {
                 return DefWindowProcW(handle, message, wparam, lparam);
             }
@@ -251,7 +367,7 @@ pub mod window_proc {
                     message,
                     wparam,
                     lparam,
-                    |hwnd, message, wparam, lparam| DefWindowProcW(hwnd, message, wparam, lparam),
+                    &|hwnd, message, wparam, lparam| DefWindowProcW(hwnd, message, wparam, lparam),
                 )
             }
         });
@@ -281,7 +397,8 @@ pub mod window_proc {
     ///   `[Box::into_raw]`. `static_subclass_wndproc` owns `p` and will eventually
     ///   release it using `drop(Box::from_raw(p))`. `[Box::into_raw]` must
     ///   have been called on the same thread which created HWND.
-    /// * dwrefdata must be `p`. It can't be null.
+    /// * dwrefdata must be `p`. If it's null, the control only gets default
+    ///   handling.
     /// * Must only be called by the Windows API.
     /// * The Windows API guarantees that it will only call this function in the
     ///   same thread that created the HWND.
@@ -294,6 +411,12 @@ pub mod window_proc {
         dwrefdata: usize,
     ) -> LRESULT {
         let p = dwrefdata as *const StaticWndprocState;
+        // Subclassing happens after creation, so unlike static_wndproc this
+        // never sees WM_GETMINMAXINFO or WM_NCCALCSIZE from before
+        // WM_NCCREATE. Still, default-handle rather than trust a null state.
+        if p.is_null() {
+            return DefSubclassProc(handle, message, wparam, lparam);
+        }
 
         // Track recursion depth
         let Some(c) = (*p).entry_count.get().checked_add(1) else {
@@ -311,16 +434,13 @@ pub mod window_proc {
                     message,
                     wparam,
                     lparam,
-                    |hwnd, message, wparam, lparam| DefSubclassProc(hwnd, message, wparam, lparam),
+                    &|hwnd, message, wparam, lparam| DefSubclassProc(hwnd, message, wparam, lparam),
                 )
             }
         });
 
-        if message == WM_NCDESTROY || res.is_err() {
-            // Schedule p destruction and prevent further calls to wndproc
-            (*p).destroy_this.set(true);
-            RemoveWindowSubclass(handle, Some(static_subclass_wndproc), 0);
-            (*p).hwnd.set(HWND(0));
+        if (message == WM_NCDESTROY || res.is_err()) && !(*p).destroy_this.get() {
+            detach_subclass(handle, p);
         }
 
         // Track recursion depth
@@ -333,5 +453,22 @@ pub mod window_proc {
 
         res.unwrap_or(LRESULT(0))
     }
+
+    /// Remove `static_subclass_wndproc` from `handle` and schedule `p`'s
+    /// destruction. Frees `p` now unless it's handling a message; the
+    /// outermost `static_subclass_wndproc` call frees it otherwise.
+    ///
+    /// # Safety
+    ///
+    /// * `handle` must be valid, with `p` as subclass 0's dwrefdata.
+    /// * Callers must not use `p` afterwards.
+    pub unsafe fn detach_subclass(handle: HWND, p: *const StaticWndprocState) {
+        (*p).destroy_this.set(true);
+        RemoveWindowSubclass(handle, Some(static_subclass_wndproc), 0);
+        (*p).hwnd.set(HWND(0));
+        if (*p).entry_count.get() == 0 {
+            drop(Box::from_raw(p as *mut StaticWndprocState));
+        }
+    }
 }
 pub use window_proc::*;
@@ -0,0 +1,117 @@
+// Backend-agnostic layout helpers
+//
+// These only use the Window trait, so they work with any WindowSystem.
+
+use crate::{Window, WindowSystem};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+/// Places children one after another along `direction`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stack {
+    pub direction: Direction,
+
+    /// Gap between adjacent children
+    pub spacing: i32,
+
+    /// Gap between the parent's client edge and the children
+    pub padding: i32,
+}
+
+impl Stack {
+    /// Position `children`, keeping their current sizes. If `client_size`
+    /// is Some, then children are also stretched across the parent's
+    /// client area, perpendicular to `direction`.
+    pub fn apply<WS: WindowSystem, C: Window<WS>>(
+        &self,
+        children: &[C],
+        client_size: Option<(i32, i32)>,
+    ) -> Result<(), WS::Error> {
+        let sizes = children
+            .iter()
+            .map(|child| child.get_bounds().map(|(_, _, w, h)| (w, h)))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (child, (x, y, w, h)) in children.iter().zip(self.arrange(&sizes, client_size)) {
+            child.clone().bounds(Some((x, y)), Some((w, h)))?;
+        }
+        Ok(())
+    }
+
+    /// (x, y, w, h) of each child, given their current sizes
+    fn arrange(
+        &self,
+        sizes: &[(i32, i32)],
+        client_size: Option<(i32, i32)>,
+    ) -> Vec<(i32, i32, i32, i32)> {
+        let mut offset = self.padding;
+        let mut result = Vec::with_capacity(sizes.len());
+        for &(mut w, mut h) in sizes {
+            let (x, y) = match self.direction {
+                Direction::Vertical => {
+                    if let Some((client_w, _)) = client_size {
+                        w = (client_w - 2 * self.padding).max(0);
+                    }
+                    (self.padding, offset)
+                }
+                Direction::Horizontal => {
+                    if let Some((_, client_h)) = client_size {
+                        h = (client_h - 2 * self.padding).max(0);
+                    }
+                    (offset, self.padding)
+                }
+            };
+            result.push((x, y, w, h));
+            offset += self.spacing
+                + match self.direction {
+                    Direction::Vertical => h,
+                    Direction::Horizontal => w,
+                };
+        }
+        result
+    }
+
+    /// Position `children` now, then re-apply whenever `parent` is resized.
+    ///
+    /// This takes over `parent`'s on_resize callback.
+    pub fn attach<WS: WindowSystem, P: Window<WS>, C: Window<WS>>(
+        self,
+        parent: &P,
+        children: Vec<C>,
+    ) -> Result<(), WS::Error> {
+        self.apply(&children, None)?;
+        parent.on_resize(move |w, h| {
+            if let Err(e) = self.apply(&children, Some((w, h))) {
+                eprintln!("Stack layout failed: {}", e);
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertical_offsets() {
+        let stack = Stack {
+            direction: Direction::Vertical,
+            spacing: 5,
+            padding: 10,
+        };
+        let buttons = [(100, 20), (80, 30), (120, 25)];
+        assert_eq!(
+            stack.arrange(&buttons, None),
+            [(10, 10, 100, 20), (10, 35, 80, 30), (10, 70, 120, 25)]
+        );
+        // Stretching changes widths but not y-offsets
+        assert_eq!(
+            stack.arrange(&buttons, Some((300, 200))),
+            [(10, 10, 280, 20), (10, 35, 280, 30), (10, 70, 280, 25)]
+        );
+    }
+}
@@ -1,22 +1,414 @@
 use closure_attr::Downgrade;
-use std::{fs::File, io::BufWriter, path::Path};
+use std::{
+    fs::File,
+    io::BufWriter,
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub mod comm_ctrl;
+pub mod layout;
 
+/// (red, green, blue, alpha). Alpha is straight, not premultiplied. Opaque
+/// GDI fills such as `background` ignore it; `Bitmap` compositing honors it.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Color(pub u8, pub u8, pub u8, pub u8);
 
+impl Color {
+    /// Fully opaque
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self(r, g, b, 255)
+    }
+
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self(r, g, b, a)
+    }
+
+    pub fn with_alpha(self, a: u8) -> Self {
+        Self(self.0, self.1, self.2, a)
+    }
+
+    /// 0x00BBGGRR; drops alpha
+    pub fn to_colorref(self) -> u32 {
+        (self.0 as u32) | ((self.1 as u32) << 8) | ((self.2 as u32) << 16)
+    }
+
+    /// Opaque color from 0x00BBGGRR
+    pub fn from_colorref(colorref: u32) -> Self {
+        Self::rgb(
+            colorref as u8,
+            (colorref >> 8) as u8,
+            (colorref >> 16) as u8,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<(i32, i32)> for Point {
+    fn from((x, y): (i32, i32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Point> for (i32, i32) {
+    fn from(p: Point) -> Self {
+        (p.x, p.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Size {
+    pub w: i32,
+    pub h: i32,
+}
+
+impl From<(i32, i32)> for Size {
+    fn from((w, h): (i32, i32)) -> Self {
+        Self { w, h }
+    }
+}
+
+impl From<Size> for (i32, i32) {
+    fn from(s: Size) -> Self {
+        (s.w, s.h)
+    }
+}
+
+/// Pointer position in client coordinates, and the modifier keys held
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub x: i32,
+    pub y: i32,
+    pub shift: bool,
+    pub control: bool,
+}
+
+/// Upper-left corner and size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn new(origin: Point, size: Size) -> Self {
+        Self {
+            x: origin.x,
+            y: origin.y,
+            w: size.w,
+            h: size.h,
+        }
+    }
+
+    pub fn origin(self) -> Point {
+        Point {
+            x: self.x,
+            y: self.y,
+        }
+    }
+
+    pub fn size(self) -> Size {
+        Size {
+            w: self.w,
+            h: self.h,
+        }
+    }
+}
+
+/// (x, y, w, h)
+impl From<(i32, i32, i32, i32)> for Rect {
+    fn from((x, y, w, h): (i32, i32, i32, i32)) -> Self {
+        Self { x, y, w, h }
+    }
+}
+
+impl From<Rect> for (i32, i32, i32, i32) {
+    fn from(r: Rect) -> Self {
+        (r.x, r.y, r.w, r.h)
+    }
+}
+
 #[non_exhaustive]
 #[derive(Clone, Debug)]
 pub enum ChildType {
-    Custom,
+    Custom(CustomOptions),
     Button,
     DefaultButton, // TODO: move into Button?
     Checkbox,
     TristateCheckbox,
+    /// Frame around sibling controls, e.g. radios; Win32 has no real
+    /// containment. Created at the bottom of z-order so it draws behind
+    /// them.
     Groupbox,
     Radio,
     Edit(EditOptions),
+    ListBox,
+    UpDown,
+    TreeView,
+
+    /// Static text. A `&` mnemonic focuses the control after it; see
+    /// `Window::label_for`.
+    Label,
+}
+
+/// One child for `Window::new_children`. Unset fields keep `new_child`'s
+/// defaults.
+#[derive(Clone, Debug)]
+pub struct ChildSpec {
+    pub ty: ChildType,
+    pub upper_left: Option<(i32, i32)>,
+    pub size: Option<(i32, i32)>,
+    pub text: Option<String>,
+}
+
+impl ChildSpec {
+    pub fn new(ty: ChildType) -> Self {
+        Self {
+            ty,
+            upper_left: None,
+            size: None,
+            text: None,
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cursor {
+    Arrow,
+    IBeam,
+    Hand,
+    Wait,
+    Cross,
+    SizeAll,
+    SizeNS,
+    SizeWE,
+    No,
+}
+
+bitflags::bitflags! {
+    /// Win32 window styles (`WS_*`) for `Window::modify_style`. Only the
+    /// window-level styles; control-specific ones in the low word, e.g.
+    /// `BS_*`, aren't covered.
+    #[derive(Default)]
+    pub struct WindowStyle: u32 {
+        const POPUP = 0x8000_0000;
+        const CHILD = 0x4000_0000;
+        const MINIMIZE = 0x2000_0000;
+        const VISIBLE = 0x1000_0000;
+        const DISABLED = 0x0800_0000;
+        const CLIP_SIBLINGS = 0x0400_0000;
+        const CLIP_CHILDREN = 0x0200_0000;
+        const MAXIMIZE = 0x0100_0000;
+        const CAPTION = 0x00c0_0000;
+        const BORDER = 0x0080_0000;
+        const DLG_FRAME = 0x0040_0000;
+        const VSCROLL = 0x0020_0000;
+        const HSCROLL = 0x0010_0000;
+        const SYS_MENU = 0x0008_0000;
+        const THICK_FRAME = 0x0004_0000;
+        /// Same bit as `MINIMIZE_BOX`; Win32 reads it by context
+        const GROUP = 0x0002_0000;
+        /// Same bit as `MAXIMIZE_BOX`; Win32 reads it by context
+        const TAB_STOP = 0x0001_0000;
+        const MINIMIZE_BOX = 0x0002_0000;
+        const MAXIMIZE_BOX = 0x0001_0000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Win32 extended window styles (`WS_EX_*`) for
+    /// `Window::modify_ex_style`
+    #[derive(Default)]
+    pub struct WindowExStyle: u32 {
+        const DLG_MODAL_FRAME = 0x0000_0001;
+        const NO_PARENT_NOTIFY = 0x0000_0004;
+        const TOPMOST = 0x0000_0008;
+        const ACCEPT_FILES = 0x0000_0010;
+        const TRANSPARENT = 0x0000_0020;
+        const TOOL_WINDOW = 0x0000_0080;
+        const WINDOW_EDGE = 0x0000_0100;
+        const CLIENT_EDGE = 0x0000_0200;
+        const CONTEXT_HELP = 0x0000_0400;
+        const RIGHT = 0x0000_1000;
+        const RTL_READING = 0x0000_2000;
+        const LEFT_SCROLLBAR = 0x0000_4000;
+        const CONTROL_PARENT = 0x0001_0000;
+        const STATIC_EDGE = 0x0002_0000;
+        const APP_WINDOW = 0x0004_0000;
+        const LAYERED = 0x0008_0000;
+        const NO_INHERIT_LAYOUT = 0x0010_0000;
+        const NO_REDIRECTION_BITMAP = 0x0020_0000;
+        const LAYOUT_RTL = 0x0040_0000;
+        const COMPOSITED = 0x0200_0000;
+        const NO_ACTIVATE = 0x0800_0000;
+    }
+}
+
+/// Frame style for top-level windows
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowChrome {
+    /// Title bar and resizable border
+    #[default]
+    Default,
+    /// No title bar or border, e.g. for splash screens
+    NoBorder,
+    /// Small title bar
+    ToolWindow,
+    /// Thin border, no title bar
+    Popup,
+}
+
+/// Minimized, maximized, or neither, for top-level windows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowState {
+    #[default]
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageButtons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+    RetryCancel,
+}
+
+/// The button the user chose
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageResult {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Retry,
+}
+
+/// Windows virtual-key code. Letters and digits are their uppercase ASCII
+/// values; see `VirtualKey::char`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VirtualKey(pub u16);
+
+impl VirtualKey {
+    pub const BACK: Self = Self(0x08);
+    pub const TAB: Self = Self(0x09);
+    pub const RETURN: Self = Self(0x0d);
+    pub const ESCAPE: Self = Self(0x1b);
+    pub const SPACE: Self = Self(0x20);
+    pub const PRIOR: Self = Self(0x21);
+    pub const NEXT: Self = Self(0x22);
+    pub const END: Self = Self(0x23);
+    pub const HOME: Self = Self(0x24);
+    pub const LEFT: Self = Self(0x25);
+    pub const UP: Self = Self(0x26);
+    pub const RIGHT: Self = Self(0x27);
+    pub const DOWN: Self = Self(0x28);
+    pub const INSERT: Self = Self(0x2d);
+    pub const DELETE: Self = Self(0x2e);
+    pub const F1: Self = Self(0x70);
+    pub const F2: Self = Self(0x71);
+    pub const F3: Self = Self(0x72);
+    pub const F4: Self = Self(0x73);
+    pub const F5: Self = Self(0x74);
+    pub const F6: Self = Self(0x75);
+    pub const F7: Self = Self(0x76);
+    pub const F8: Self = Self(0x77);
+    pub const F9: Self = Self(0x78);
+    pub const F10: Self = Self(0x79);
+    pub const F11: Self = Self(0x7a);
+    pub const F12: Self = Self(0x7b);
+
+    /// Key for an ASCII letter or digit, e.g. `char('s')` for Ctrl+S
+    pub fn char(c: char) -> Self {
+        Self(c.to_ascii_uppercase() as u16)
+    }
+}
+
+/// Keyboard shortcut which sends `command_id` to `on_command`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Accelerator {
+    pub key: VirtualKey,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub command_id: u16,
+}
+
+/// Returned from `on_close` to decide whether the window is destroyed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CloseAction {
+    #[default]
+    Close,
+    Cancel,
+}
+
+#[derive(Clone, Debug)]
+pub struct CustomOptions {
+    pub clip_siblings: bool,
+    pub clip_children: bool,
+    pub hscroll: bool,
+    pub vscroll: bool,
+    pub border: bool,
+}
+
+impl Default for CustomOptions {
+    fn default() -> Self {
+        Self {
+            clip_siblings: true,
+            clip_children: false,
+            hscroll: false,
+            vscroll: false,
+            border: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollBar {
+    Horizontal,
+    Vertical,
+}
+
+/// User interaction with a scroll bar. For horizontal bars, Up means left and
+/// Down means right.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollRequest {
+    LineUp,
+    LineDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    /// Thumb is being dragged to this position
+    ThumbTrack(i32),
+    /// Thumb was released at this position
+    ThumbPosition(i32),
+    EndScroll,
+}
+
+/// Scroll bar state passed to `on_scroll`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScrollPos {
+    pub request: ScrollRequest,
+    /// Position before the window responds to `request`
+    pub position: i32,
+    pub min: i32,
+    pub max: i32,
+    pub page: i32,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -39,36 +431,520 @@ pub trait WindowSystem: Clone + Downgrade + 'static {
     type Error: std::error::Error;
     type Window: Window<Self>;
     type Child: Window<Self>;
+    type Button: Button<Self>;
+    type Edit: Edit<Self>;
+    type ListBox: ListBox<Self>;
+    type UpDown: UpDown<Self>;
+    type TreeView: TreeView<Self>;
 
+    /// Create a hidden top-level window. Add children and set attributes,
+    /// then show it with `visible(true)`; showing a window before its
+    /// contents are ready flashes an unpainted frame.
     fn new_main(&self) -> Result<Self::Window, Self::Error>;
+
+    /// `new_main` followed by `visible(true)`, for windows which don't need
+    /// setting up first
+    fn new_main_visible(&self) -> Result<Self::Window, Self::Error>;
+
+    /// Create a window which never displays, but can receive messages.
+    /// Display-related methods (e.g. `bounds`, `snapshot`) fail on it.
+    fn new_message_only(&self) -> Result<Self::Window, Self::Error>;
     fn event_loop(&self) -> Result<(), Self::Error>;
+
+    /// Like `event_loop`, but also exits once `duration` has elapsed. Useful
+    /// for CI jobs which must not hang if a window never closes.
+    fn run_with_timeout(&self, duration: Duration) -> Result<(), Self::Error>;
     fn exit_loop(&self) -> Result<(), Self::Error>;
+
+    /// `event_loop` which exits once `main_window` is destroyed, for simple
+    /// single-window apps. Doesn't replace `main_window`'s `on_destroy`.
+    /// Use `event_loop` and `exit_loop` when several windows decide.
+    fn run(&self, main_window: &Self::Window) -> Result<(), Self::Error>;
+
+    /// Every window on this thread which hasn't been destroyed. Useful for
+    /// checking that a test didn't leak windows.
+    fn live_windows(&self) -> Vec<Self::Window>;
+
+    /// The active top-level window on this thread, if this library created it
+    fn active_window(&self) -> Option<Self::Window>;
+
+    /// Show a modal message box; modal to `parent` if given. Closing it
+    /// with Esc or the title bar reports `Cancel` (or `Ok` for `Ok` only).
+    fn message_box(
+        &self,
+        parent: Option<&Self::Window>,
+        title: &str,
+        text: &str,
+        buttons: MessageButtons,
+    ) -> Result<MessageResult, Self::Error>;
 }
 
 pub trait Window<WS: WindowSystem>: Clone + Downgrade + 'static {
     fn system(&self) -> WS;
     fn destroy(&self) -> Result<(), WS::Error>;
+
+    /// Dynamically-typed child creation. Prefer the typed constructors
+    /// below, which only expose methods valid for that control.
     fn new_child(&self, ty: ChildType) -> Result<WS::Child, WS::Error>;
 
+    /// Create several children at once, in order. Cheaper than repeated
+    /// `new_child` plus `bounds` and `text`, e.g. for large forms. Stops at
+    /// the first failure; children created before it remain.
+    fn new_children(&self, specs: &[ChildSpec]) -> Result<Vec<WS::Child>, WS::Error>;
+    fn new_button(&self) -> Result<WS::Button, WS::Error>;
+    fn new_edit(&self, options: EditOptions) -> Result<WS::Edit, WS::Error>;
+    fn new_list_box(&self) -> Result<WS::ListBox, WS::Error>;
+    fn new_up_down(&self) -> Result<WS::UpDown, WS::Error>;
+    fn new_tree_view(&self) -> Result<WS::TreeView, WS::Error>;
+
+    /// Caption of a top-level window or label of a control. See `title`.
     fn text(self, text: &str) -> Result<Self, WS::Error>;
+
+    /// `text` for the title bar caption; fails on child windows. Prefer it
+    /// for top-level windows so the intent is clear.
+    fn title(self, title: &str) -> Result<Self, WS::Error>;
+
+    /// `get_text` for the title bar caption; fails on child windows
+    fn get_title(&self) -> Result<String, WS::Error>;
+
+    /// Caption or control text. Round trips `text` exactly, including
+    /// surrogate pairs and combining marks; no normalization is applied.
+    /// Malformed UTF-16 the user entered, e.g. a lone surrogate, decodes to
+    /// U+FFFD.
+    fn get_text(&self) -> Result<String, WS::Error>;
     fn bounds(
         self,
         upper_left: Option<(i32, i32)>,
         size: Option<(i32, i32)>,
     ) -> Result<Self, WS::Error>;
 
+    /// (x, y, w, h) in screen coordinates for top-level windows and
+    /// parent-client coordinates for children; the same space `bounds` uses.
+    fn get_bounds(&self) -> Result<(i32, i32, i32, i32), WS::Error>;
+
+    /// `bounds` with both the position and size
+    fn set_bounds(self, rect: Rect) -> Result<Self, WS::Error>;
+
+    /// `get_bounds` as a Rect
+    fn get_rect(&self) -> Result<Rect, WS::Error>;
+
+    /// Move to the center of the work area of the monitor holding most of
+    /// the window. Keeps the size.
+    fn center_on_screen(self) -> Result<Self, WS::Error>;
+
+    /// Move a child to the center of its parent's client area, or a
+    /// top-level window to the center of its owner, kept within the work
+    /// area. Without an owner, same as `center_on_screen`. Keeps the size.
+    fn center_on_parent(self) -> Result<Self, WS::Error>;
+
+    /// (width, height) in pixels of text drawn in this window's font.
+    /// Multiline text measures the widest line and the total height.
+    fn measure_text(&self, text: &str) -> Result<(i32, i32), WS::Error>;
+
+    /// Visible child under (x, y) in this window's client coordinates. None
+    /// if the point hits this window itself or a window this library didn't
+    /// create.
+    fn child_at(&self, x: i32, y: i32) -> Option<WS::Child>;
+
+    /// None for top-level and destroyed windows
+    fn parent(&self) -> Option<WS::Window>;
+
+    /// Id the parent's WM_COMMAND and WM_NOTIFY use to identify this child,
+    /// unique among its siblings. None for top-level windows.
+    fn control_id(&self) -> Option<u16>;
+
+    /// Children created through this window. Empty once destroyed.
+    fn children(&self) -> Vec<WS::Child>;
+
     // TODO: standard color support (e.g. COLOR_BTNFACE)
     fn background(self, color: Color) -> Result<Self, WS::Error>;
 
-    // TODO: option to not activate and to not go on the taskbar
+    /// Keep the window off the taskbar and out of Alt+Tab. Apply before the
+    /// window is first shown.
+    fn tool_window(self) -> Result<Self, WS::Error>;
+
+    /// Change the frame of a top-level window. Apply before the window is
+    /// first shown. Without a title bar the user can't move the window; pair
+    /// with `draggable`.
+    fn chrome(self, chrome: WindowChrome) -> Result<Self, WS::Error>;
+
+    /// Show or hide the window's own scroll bars
+    fn enable_scrollbars(self, horizontal: bool, vertical: bool) -> Result<Self, WS::Error>;
+
+    /// `page` is the visible amount, which sizes the thumb
+    fn set_scroll_range(
+        &self,
+        bar: ScrollBar,
+        min: i32,
+        max: i32,
+        page: i32,
+    ) -> Result<(), WS::Error>;
+
+    /// Clamped to the range
+    fn set_scroll_position(&self, bar: ScrollBar, position: i32) -> Result<(), WS::Error>;
+
+    /// Mirror the layout for right-to-left languages. Children created
+    /// afterwards inherit the mirroring, so apply before creating children.
+    /// In a mirrored window, child x coordinates (`bounds`, `get_bounds`,
+    /// `child_at`) are measured from the right edge of the client area.
+    fn rtl(self, on: bool) -> Result<Self, WS::Error>;
+
+    /// Replace the window's keyboard shortcuts; an empty slice removes them.
+    /// Only tables on top-level windows are used, for messages to the window
+    /// or any of its descendants.
+    fn set_accelerators(&self, accels: &[Accelerator]) -> Result<(), WS::Error>;
+
+    /// Make this `ChildType::Label`'s mnemonic focus `target`. Alt plus the
+    /// letter after `&` moves focus to the first tab stop following the
+    /// label in z-order, which is creation order unless changed; this moves
+    /// the label directly before `target`, so call it after both exist.
+    /// Later z-order changes to either can separate them again. Fails if
+    /// they don't share a parent.
+    fn label_for(&self, target: &WS::Child) -> Result<(), WS::Error>;
+
+    /// Start a new group of controls at this one. Arrow keys move within a
+    /// group, and checking a `ChildType::Radio` unchecks the other radios in
+    /// its group. A group runs, in creation order, until the next control
+    /// which begins one:
+    ///
+    /// ```text
+    /// parent.new_child(ChildType::Radio)?.begin_group()?; // group 1
+    /// parent.new_child(ChildType::Radio)?;                // group 1
+    /// parent.new_child(ChildType::Radio)?.begin_group()?; // group 2
+    /// ```
+    fn begin_group(self) -> Result<Self, WS::Error>;
+
+    /// Let the user move the window by dragging anywhere in its client area.
+    /// Use `drag_filter` to keep interactive regions clickable.
+    fn draggable(self, on: bool) -> Result<Self, WS::Error>;
+
+    /// Make a top-level window layered, so a `background` with alpha below
+    /// 255 is drawn translucent, covering the frame too; pair it with
+    /// `WindowChrome::NoBorder`. Without a `background`, the window is
+    /// filled with the system window color. `on_paint` isn't called while
+    /// layered. Ignored for child windows.
+    fn layered(self) -> Result<Self, WS::Error>;
+
+    /// Make every pixel of exactly `color` (ignoring alpha) invisible and
+    /// click-through, e.g. for shaped windows: paint the background
+    /// `color` and draw the shape over it. Makes the window layered.
+    /// Translucent `layered` backgrounds stop working, since Win32 can't
+    /// mix the two kinds of layered window. Win32 can combine a color key
+    /// with uniform alpha (`LWA_COLORKEY | LWA_ALPHA`), but this sets only
+    /// the key. To let input pass through the visible pixels too, the
+    /// window also needs `WS_EX_TRANSPARENT`. Ignored for child windows.
+    fn transparent_color(self, color: Color) -> Result<Self, WS::Error>;
+
+    /// Let mouse input pass through the whole window to whatever is
+    /// beneath, including other applications' windows, e.g. for overlay
+    /// HUDs. Makes the window layered while on. Ignored for child windows.
+    /// For only some regions, or for children, use `click_through_filter`.
+    fn click_through(self, on: bool) -> Result<Self, WS::Error>;
+
+    /// Draw a top-level window's title bar and frame dark. Only the
+    /// non-client frame changes; the client area and controls keep their
+    /// colors. Ignored for child windows and before Windows 10 1809.
+    fn dark_mode(self, on: bool) -> Result<Self, WS::Error>;
+
+    /// Don't activate the window when it's shown or clicked. Apply before the
+    /// window is first shown.
+    fn no_activate(self) -> Result<Self, WS::Error>;
+
+    /// Add and remove window styles, then redraw the frame to match. A
+    /// low-level escape hatch; prefer the specific methods, e.g. `chrome`,
+    /// which also keep their own state in sync.
+    fn modify_style(self, add: WindowStyle, remove: WindowStyle) -> Result<Self, WS::Error>;
+
+    /// `modify_style` for extended styles
+    fn modify_ex_style(self, add: WindowExStyle, remove: WindowExStyle) -> Result<Self, WS::Error>;
+
+    /// Expose `id` as the UI Automation `AutomationId`, which test tools
+    /// such as WinAppDriver and Inspect use to find controls. Only that
+    /// property is set; the accessible name still comes from the window's
+    /// text, which MSAA clients see too. Needs COM on this thread, e.g.
+    /// `comm_ctrl::System::with_com`.
+    fn automation_id(self, id: &str) -> Result<Self, WS::Error>;
+
+    /// Combine with `tool_window` and `no_activate` for windows which should
+    /// never be seen, e.g. when rendering snapshots.
     fn move_offscreen(self) -> Result<Self, WS::Error>;
     fn visible(self, visible: bool) -> Result<Self, WS::Error>;
+
+    /// Show the window once it has painted, so slow-initializing windows
+    /// don't flash a blank frame. No-op if the window was already shown.
+    fn show_when_ready(self) -> Result<Self, WS::Error>;
+
+    /// Minimize, maximize, or restore. Shows a hidden window.
+    fn window_state(self, state: WindowState) -> Result<Self, WS::Error>;
+    fn get_window_state(&self) -> Result<WindowState, WS::Error>;
+
+    /// Bring a top-level window to the foreground and activate it. Windows
+    /// only allows this when the calling process owns the foreground window
+    /// or was granted permission (e.g. it was just launched or received the
+    /// last input); otherwise this fails and the taskbar button flashes
+    /// instead.
+    fn activate(&self) -> Result<(), WS::Error>;
+
+    /// Cursor shown over the client area. Defaults to an arrow.
+    fn cursor(self, cursor: Cursor) -> Result<Self, WS::Error>;
+
+    /// Send all mouse input to this window until `release_mouse`, even when
+    /// the pointer leaves it, e.g. while dragging on a custom canvas.
+    /// Coordinates outside the client area, including negative ones, are
+    /// reported relative to it. See `on_capture_lost`.
+    fn capture_mouse(&self) -> Result<(), WS::Error>;
+
+    /// End `capture_mouse`. Does nothing if another window has the capture.
+    fn release_mouse(&self) -> Result<(), WS::Error>;
+
+    /// Repaint the whole window, erasing its background first
     fn redraw(self) -> Result<Self, WS::Error>;
+
+    /// Repaint part of the client area, in client coordinates. `erase`
+    /// fills the background first. Cheaper than `redraw` for incremental
+    /// updates.
+    fn invalidate_rect(&self, rect: Rect, erase: bool) -> Result<(), WS::Error>;
+
+    /// Cancel pending repaints, e.g. after drawing the latest state directly
+    fn validate(&self) -> Result<(), WS::Error>;
+
+    /// Paint the invalid region now rather than when the message loop next
+    /// idles, e.g. between `redraw` and `snapshot`. Does nothing if nothing
+    /// is invalid.
+    fn update(&self) -> Result<(), WS::Error>;
+
+    /// Clip the window, frame included, to the union of `rects`, in
+    /// coordinates relative to its upper-left corner. Nothing outside is
+    /// drawn or hit. An empty slice removes the region, restoring the full
+    /// window.
+    fn set_region(&self, rects: &[Rect]) -> Result<(), WS::Error>;
+
+    /// `set_region` with a `w` by `h` rectangle whose corners are rounded by
+    /// an ellipse of `ellipse_w` by `ellipse_h`
+    fn set_round_region(
+        &self,
+        w: i32,
+        h: i32,
+        ellipse_w: i32,
+        ellipse_h: i32,
+    ) -> Result<(), WS::Error>;
+
+    /// Capture the window, including its frame and children. Works on
+    /// hidden windows: backgrounds and the standard controls (buttons,
+    /// edits, list boxes) render without being shown. `on_paint` content
+    /// and controls which don't handle WM_PRINTCLIENT need the window
+    /// visible; combine `move_offscreen` with `visible(true)` to keep it out
+    /// of sight.
     fn snapshot(&self) -> Result<Bitmap, WS::Error>;
 
-    fn on_close<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+    /// `snapshot` in `PixelOrder::Bgra`, GDI's output verbatim, for
+    /// consumers which want BGRA without a swap
+    fn snapshot_raw(&self) -> Result<Bitmap, WS::Error>;
+
+    /// Called when the user asks to close the window. Return
+    /// `CloseAction::Cancel` to keep it open, e.g. to prompt about unsaved
+    /// changes. Without a callback the window is destroyed.
+    fn on_close<F: FnMut() -> CloseAction + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Drop every registered callback. A callback which captures its own
+    /// window, or an ancestor, strongly forms a reference cycle. Destroying
+    /// the window clears its callbacks and breaks the cycle, but a window
+    /// which is never destroyed leaks. Prefer weak captures or
+    /// `on_close_self`, or call this when done with the window.
+    fn clear_callbacks(&self) -> Result<(), WS::Error>;
+
+    /// `on_close` with the window passed in, so the callback doesn't need
+    /// to capture it (which would leak it through a reference cycle) or
+    /// capture a weak handle with `closure_attr`.
+    fn on_close_self<F: FnMut(&WS::Window) -> CloseAction + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Called as destruction starts, while this window and its children are
+    /// still alive, e.g. to save their state. Teardown runs in this order:
+    ///
+    /// 1. `on_close`, if the user closed the window
+    /// 2. `on_destroy`, for this window, then its descendants
+    /// 3. `on_final`, for the descendants, deepest first, then this window
     fn on_destroy<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
-    // TODO: mouse, set cursor
+
+    /// Last-chance cleanup, called after this window has released its
+    /// children. The window can no longer be used.
+    fn on_final<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Observe every message the window receives, before normal handling.
+    /// Callback receives (message, wparam, lparam) and returns true to mark the
+    /// message handled, which skips normal handling. WM_NCDESTROY cleanup always
+    /// runs regardless of the return value.
+    fn on_message<F: FnMut(u32, usize, isize) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Callback receives the new client (width, height)
+    fn on_resize<F: FnMut(i32, i32) + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Called with the window's new outer rect, in screen coordinates even
+    /// for children, after it moves or resizes. Unlike `on_resize`, this
+    /// reports moves and includes the frame.
+    fn on_window_pos_changed<F: FnMut(Rect) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Called when the window is minimized, maximized, or restored, whether
+    /// by the user or `window_state`
+    fn on_state_changed<F: FnMut(WindowState) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Called when system settings or the visual theme change, e.g. colors,
+    /// metrics, or the message font. Every top-level window is notified;
+    /// children only hear of theme changes. Windows using the system font
+    /// have already switched to the new one.
+    fn on_settings_changed<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Called when the window loses the mouse capture, whether by
+    /// `release_mouse` or because the system or another window took it.
+    /// Cancel any drag in progress here.
+    fn on_capture_lost<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Called on a left-button double-click. The first click of the pair
+    /// arrives as an ordinary click; the second only as this.
+    fn on_double_click<F: FnMut(MouseEvent) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Called with the command id when an accelerator or menu item fires
+    fn on_command<F: FnMut(u16) + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Called when the user interacts with the window's own scroll bars (see
+    /// `enable_scrollbars`). The window must call `set_scroll_position` to
+    /// move the thumb.
+    fn on_scroll<F: FnMut(ScrollBar, ScrollPos) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Decides whether a client point (x, y) drags a `draggable` window.
+    /// Without a filter the whole client area drags.
+    fn drag_filter<F: FnMut(i32, i32) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Decides whether clicks at a client point (x, y) pass through to the
+    /// window beneath. Unlike `click_through`, this works per point, but
+    /// Win32 (`HTTRANSPARENT`) only passes them to windows on the same
+    /// thread, e.g. siblings and the parent.
+    fn click_through_filter<F: FnMut(i32, i32) -> bool + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+
+    /// Accept files dragged from the shell. Called with the dropped paths.
+    fn on_files_dropped<F: FnMut(Vec<PathBuf>) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+    // TODO: mouse
+}
+
+/// Push button. Derefs to the untyped child for shared attributes.
+pub trait Button<WS: WindowSystem>: Window<WS> + Deref<Target = WS::Child> {
+    /// Make this the button Enter presses, and draw it with the default
+    /// highlight. The parent's previous default button is demoted.
+    /// `ChildType::DefaultButton` does this on creation.
+    fn set_default(&self) -> Result<(), WS::Error>;
+}
+
+/// Text entry. Derefs to the untyped child for shared attributes.
+pub trait Edit<WS: WindowSystem>: Window<WS> + Deref<Target = WS::Child> {
+    /// Called after the text changes. Fires once per keystroke and also when
+    /// the text is set programmatically (e.g. by `text`); there's no debouncing.
+    fn on_text_changed<F: FnMut() + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+
+    /// Select UTF-16 positions `start..end`. `end == u32::MAX` selects to the
+    /// end of the text.
+    fn set_selection(&self, start: u32, end: u32) -> Result<(), WS::Error>;
+    fn selected_text(&self) -> Result<String, WS::Error>;
+
+    /// Replace the selection, or insert at the caret if nothing is selected
+    fn replace_selection(&self, text: &str) -> Result<(), WS::Error>;
+
+    /// Fails on single-line edits
+    fn line_count(&self) -> Result<u32, WS::Error>;
+
+    /// Scroll so `line` is the first visible line. Fails on single-line edits.
+    fn scroll_to_line(&self, line: u32) -> Result<(), WS::Error>;
+
+    /// Maximum characters the user can enter; 0 means the maximum the
+    /// control supports. The default is 32767 characters.
+    fn set_text_limit(&self, chars: u32) -> Result<(), WS::Error>;
+}
+
+/// List of strings. Derefs to the untyped child for shared attributes.
+pub trait ListBox<WS: WindowSystem>: Window<WS> + Deref<Target = WS::Child> {
+    /// Append an item
+    fn add_string(&self, text: &str) -> Result<(), WS::Error>;
+}
+
+/// Spinner arrows, usually attached to an edit buddy
+pub trait UpDown<WS: WindowSystem>: Window<WS> + Deref<Target = WS::Child> {
+    /// Attach to a control which displays the value, typically an edit
+    fn set_buddy(&self, buddy: &WS::Child) -> Result<(), WS::Error>;
+    fn set_range(&self, min: i32, max: i32) -> Result<(), WS::Error>;
+    fn set_position(&self, position: i32) -> Result<(), WS::Error>;
+
+    /// Called with the new value when the user clicks an arrow or presses an
+    /// arrow key. `set_position` doesn't call it.
+    fn on_value_changed<F: FnMut(i32) + 'static>(&self, callback: F) -> Result<&Self, WS::Error>;
+}
+
+/// Node in a `TreeView`. Only meaningful to the tree which created it;
+/// methods fail once the item (or an ancestor) has been deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TreeItem(pub(crate) isize);
+
+/// Hierarchical list. Derefs to the untyped child for shared attributes.
+pub trait TreeView<WS: WindowSystem>: Window<WS> + Deref<Target = WS::Child> {
+    /// Append an item under `parent`, or at the root if None
+    fn insert_item(&self, parent: Option<TreeItem>, text: &str) -> Result<TreeItem, WS::Error>;
+
+    /// Delete `item` and its descendants
+    fn delete_item(&self, item: TreeItem) -> Result<(), WS::Error>;
+
+    /// Show or hide `item`'s children
+    fn expand(&self, item: TreeItem, expand: bool) -> Result<(), WS::Error>;
+
+    /// Select `item`, expanding its ancestors so it's visible. Calls
+    /// `on_selection_changed` like a user selection does.
+    fn select(&self, item: TreeItem) -> Result<(), WS::Error>;
+
+    /// Called with the newly selected item, whether the user or `select`
+    /// changed it
+    fn on_selection_changed<F: FnMut(TreeItem) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<&Self, WS::Error>;
+}
+
+/// Layout of each pixel in `Bitmap::data`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelOrder {
+    /// 0xAABBGGRR, i.e. bytes R, G, B, A in memory. Snapshots are opaque.
+    #[default]
+    Rgba,
+
+    /// 0xAARRGGBB, i.e. bytes B, G, R, A in memory; GDI's native layout.
+    /// Snapshots leave alpha as GDI wrote it, usually 0.
+    Bgra,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -76,8 +952,14 @@ pub struct Bitmap {
     pub width: u32,
     pub height: u32,
 
-    /// 0xAABBGGRR, length = width * height
+    /// Rows top to bottom, length = width * height
     pub data: Vec<u32>,
+    pub order: PixelOrder,
+}
+
+/// Convert between `PixelOrder::Rgba` and `PixelOrder::Bgra`
+pub(crate) fn swap_red_blue(p: u32) -> u32 {
+    p & 0xff00ff00 | (p & 0xff) << 16 | (p >> 16) & 0xff
 }
 
 impl Bitmap {
@@ -88,7 +970,13 @@ impl Bitmap {
         png.set_color(png::ColorType::Rgba);
         png.set_depth(png::BitDepth::Eight);
         let mut writer = png.write_header()?;
-        writer.write_image_data(bytemuck::cast_slice(&self.data))?;
+        match self.order {
+            PixelOrder::Rgba => writer.write_image_data(bytemuck::cast_slice(&self.data))?,
+            PixelOrder::Bgra => {
+                let swapped: Vec<u32> = self.data.iter().map(|&p| swap_red_blue(p)).collect();
+                writer.write_image_data(bytemuck::cast_slice(&swapped))?
+            }
+        }
         Ok(())
     }
 }
@@ -7,18 +7,18 @@ fn make<WS: ::trywin::WindowSystem>(ws: WS) -> Result<WS::Window, WS::Error> {
     let window = ws
         .new_main()?
         .bounds(None, Some((500, 300)))?
-        .text("Hello, world!")?
+        .title("Hello, world!")?
         .background(Color(128, 128, 128, 0))?;
     let color1 = window
-        .new_child(ChildType::Custom)?
+        .new_child(ChildType::Custom(Default::default()))?
         .bounds(Some((10, 10)), Some((50, 50)))?
         .background(Color(255, 0, 0, 255))?;
     let color2 = window
-        .new_child(ChildType::Custom)?
+        .new_child(ChildType::Custom(Default::default()))?
         .bounds(Some((70, 10)), Some((50, 50)))?
         .background(Color(0, 255, 0, 255))?;
     let color3 = window
-        .new_child(ChildType::Custom)?
+        .new_child(ChildType::Custom(Default::default()))?
         .bounds(Some((130, 10)), Some((50, 50)))?
         .background(Color(0, 0, 255, 255))?;
     let button1 = window
@@ -68,16 +68,22 @@ fn make<WS: ::trywin::WindowSystem>(ws: WS) -> Result<WS::Window, WS::Error> {
         edit: edit.clone(),
     });
 
+    // Returning CloseAction::Cancel would keep the window open
     window.on_close(
-        #[closure(weak window)]
+        #[closure(weak edit)]
         move || {
-            window.destroy().unwrap();
+            println!(
+                "Closing with {} bytes of text",
+                edit.get_text().unwrap().len()
+            );
+            CloseAction::Close
         },
     )?;
 
+    // Children are still alive here
     window.on_destroy(
-        #[closure(weak ws)]
-        move || ws.exit_loop().unwrap(),
+        #[closure(weak edit)]
+        move || println!("Edit text: {:?}", edit.get_text().unwrap()),
     )?;
 
     Ok(window)
@@ -93,10 +99,12 @@ where
 fn main() -> Result<(), Box<dyn Error>> {
     use trywin::{comm_ctrl::System, Window, WindowSystem};
 
-    let _w = make(System.clone())?;
+    let system = System::new();
+    system.enable_visual_styles()?;
+    let _w = make(system.clone())?;
     // let _w = _w.move_offscreen()?;
     let _w = _w.visible(true)?;
     _w.snapshot()?.save_png(Path::new("snapshot.png"))?;
-    System.event_loop()?;
+    system.run(&_w)?;
     Ok(())
 }